@@ -0,0 +1,126 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+@group(2) @binding(100)
+var mat_array_texture: texture_2d_array<f32>;
+
+@group(2) @binding(101)
+var mat_array_texture_sampler: sampler;
+
+// Must match the vertex output of the bevy_voxel_world vertex shader
+struct CustomVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_UVS_B
+    @location(3) uv_b: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(4) world_tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    @location(6) @interpolate(flat) instance_index: u32,
+#endif
+
+    @location(8) tex_idx: vec3<u32>,
+}
+
+// Keep in sync with TILE_ROTATION_SHIFT and TILE_TINT_SHIFT in textures.rs
+const TILE_ROTATION_SHIFT: u32 = 16u;
+const TILE_TINT_SHIFT: u32 = 18u;
+const TILE_LAYER_MASK: u32 = 0xffffu;
+
+// Tint levels from dry (1) to lush (7), level 0 is untinted
+const TINTS = array<vec3<f32>, 8>(
+    vec3<f32>(1.0, 1.0, 1.0),
+    vec3<f32>(1.25, 1.05, 0.55),
+    vec3<f32>(1.15, 1.05, 0.7),
+    vec3<f32>(1.05, 1.02, 0.85),
+    vec3<f32>(1.0, 1.0, 1.0),
+    vec3<f32>(0.9, 1.0, 0.9),
+    vec3<f32>(0.78, 0.95, 0.8),
+    vec3<f32>(0.65, 0.9, 0.72),
+);
+
+// Emitted light of a face fully lit by block light, vertex color alpha holds the light level
+const BLOCK_LIGHT_NITS: f32 = 400.0;
+
+// Rotate the uv of a unit quad by a number of quarter turns around the quad center
+fn rotate_uv(uv: vec2<f32>, quarter_turns: u32) -> vec2<f32> {
+    var rotated = uv;
+    switch quarter_turns {
+        case 1u: { rotated = vec2<f32>(1.0 - uv.y, uv.x); }
+        case 2u: { rotated = vec2<f32>(1.0 - uv.x, 1.0 - uv.y); }
+        case 3u: { rotated = vec2<f32>(uv.y, 1.0 - uv.x); }
+        default: {}
+    }
+    return rotated;
+}
+
+@fragment
+fn fragment(
+    in: CustomVertexOutput,
+    @builtin(front_facing) is_front: bool,
+)  -> FragmentOutput {
+    var standard_in: VertexOutput;
+    standard_in.position = in.position;
+    standard_in.world_normal = in.world_normal;
+    standard_in.world_position = in.world_position;
+    standard_in.uv = in.uv;
+    standard_in.color = in.color;
+    standard_in.instance_index = in.instance_index;
+    var pbr_input = pbr_input_from_standard_material(standard_in, is_front);
+
+    var tex_face = 0;
+
+    // determine texture index based on normal
+    if in.world_normal.y == 0.0 {
+        tex_face = 1;
+    } else if in.world_normal.y < 0.0 {
+        tex_face = 2;
+    }
+
+    let tile = in.tex_idx[tex_face];
+    let layer = tile & TILE_LAYER_MASK;
+    let uv = rotate_uv(in.uv, (tile >> TILE_ROTATION_SHIFT) & 3u);
+
+    pbr_input.material.base_color = textureSample(mat_array_texture, mat_array_texture_sampler, uv, layer);
+    let tint = TINTS[(tile >> TILE_TINT_SHIFT) & 7u];
+    pbr_input.material.base_color = vec4<f32>(pbr_input.material.base_color.rgb * tint, pbr_input.material.base_color.a);
+    // Vertex color holds the ambient occlusion in rgb and the block light in alpha
+    pbr_input.material.base_color = vec4<f32>(pbr_input.material.base_color.rgb * in.color.rgb, pbr_input.material.base_color.a);
+    pbr_input.material.emissive = vec4<f32>(pbr_input.material.base_color.rgb * in.color.a * BLOCK_LIGHT_NITS, 1.0);
+
+    // Fence, glass and track tiles are cut out using the tile alpha
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
@@ -1 +1,896 @@
+//! Terrain editor for the maps of the demo. The map is drawn from above, the panel on the left
+//! generates a new one and the panel on the right picks the tool, the surface it paints and the
+//! layers that are drawn. Holding the left mouse button on the map uses the tool.
 
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{TextureDimension, TextureFormat};
+use bevy::ui::RelativeCursorPosition;
+use std::path::{Path, PathBuf};
+use voxel_demo::map_file::{MAPS_DIR, MAP_EXTENSION};
+use voxel_demo::options::{self, MAX_MAP_SIZE, MAX_OCTAVES};
+use voxel_demo::textures::map_shade;
+use voxel_demo::{GenParams, Map, MapFile, MapNode, NodeType, Size};
+
+const USAGE: &str = "Usage: editor [map file]
+
+Edits the map file, a new map is generated if it doesn't exist yet [default: assets/maps/editor.map]
+";
+
+/// Map sizes change by this many columns.
+const SIZE_STEP: u32 = 16;
+const MAX_RADIUS: u32 = 16;
+/// Seconds between two uses of the tool while the mouse button is held.
+const STROKE_INTERVAL: f32 = 0.05;
+/// Height between two contour lines.
+const CONTOUR_STEP: i32 = 5;
+const GROUND_COLOR: [u8; 3] = [140, 135, 130];
+const WET_COLOR: [u8; 3] = [30, 80, 200];
+const CONTOUR_COLOR: [u8; 3] = [40, 30, 20];
+const PANEL_COLOR: Color = Color::srgb(0.12, 0.12, 0.14);
+const BUTTON_COLOR: Color = Color::srgb(0.25, 0.25, 0.3);
+const HOVERED_BORDER: Color = Color::srgb(0.5, 0.5, 0.55);
+const SELECTED_BORDER: Color = Color::WHITE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Raise,
+    Lower,
+    Paint,
+    Smooth,
+}
+
+impl Tool {
+    const ALL: [Tool; 4] = [Tool::Raise, Tool::Lower, Tool::Paint, Tool::Smooth];
+}
+
+/// What the map view draws, every layer can be hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    /// The block at the top of each column, or plain ground when hidden.
+    Surface,
+    /// Brighter the higher a column is, lit from the north west.
+    Shading,
+    Moisture,
+    Water,
+    Contours,
+}
+
+impl Layer {
+    const ALL: [Layer; 5] = [
+        Layer::Surface,
+        Layer::Shading,
+        Layer::Moisture,
+        Layer::Water,
+        Layer::Contours,
+    ];
+}
+
+/// A generation parameter, or the size of the map generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Param {
+    Width,
+    Depth,
+    Seed,
+    Octaves,
+    Frequency,
+    Lacunarity,
+    Persistence,
+    Amplitude,
+}
+
+impl Param {
+    const ALL: [Param; 8] = [
+        Param::Width,
+        Param::Depth,
+        Param::Seed,
+        Param::Octaves,
+        Param::Frequency,
+        Param::Lacunarity,
+        Param::Persistence,
+        Param::Amplitude,
+    ];
+
+    fn value(&self, params: &GenParams, size: UVec2) -> String {
+        match self {
+            Param::Width => size.x.to_string(),
+            Param::Depth => size.y.to_string(),
+            Param::Seed => params.seed.to_string(),
+            Param::Octaves => params.octaves.to_string(),
+            Param::Frequency => format!("{:.2}", params.frequency),
+            Param::Lacunarity => format!("{:.2}", params.lacunarity),
+            Param::Persistence => format!("{:.2}", params.persistence),
+            Param::Amplitude => format!("{:.0}", params.amplitude),
+        }
+    }
+
+    /// Change the parameter by `steps` steps, keeping it in range.
+    fn step(&self, params: &mut GenParams, size: &mut UVec2, steps: i32) {
+        // Rounded so repeated steps don't drift
+        let add = |value: f64, step: f64, min: f64| {
+            ((value + step * steps as f64) * 100.0)
+                .round()
+                .max(min * 100.0)
+                / 100.0
+        };
+        let resize = |value: u32| {
+            value
+                .saturating_add_signed(steps * SIZE_STEP as i32)
+                .clamp(SIZE_STEP, MAX_MAP_SIZE)
+        };
+        match self {
+            Param::Width => size.x = resize(size.x),
+            Param::Depth => size.y = resize(size.y),
+            Param::Seed => params.seed = params.seed.wrapping_add_signed(steps),
+            Param::Octaves => {
+                params.octaves = params
+                    .octaves
+                    .saturating_add_signed(steps as isize)
+                    .clamp(1, MAX_OCTAVES)
+            }
+            Param::Frequency => params.frequency = add(params.frequency, 0.1, 0.1),
+            Param::Lacunarity => params.lacunarity = add(params.lacunarity, 0.1, 0.1),
+            Param::Persistence => params.persistence = add(params.persistence, 0.05, 0.05),
+            Param::Amplitude => params.amplitude = add(params.amplitude, 5.0, 5.0),
+        }
+    }
+}
+
+/// What a button of the panels does.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+enum EditorButton {
+    Step(Param, i32),
+    Generate,
+    Tool(Tool),
+    Radius(i32),
+    Surface(NodeType),
+    Layer(Layer),
+    Save,
+    Load,
+}
+
+#[derive(Resource)]
+struct Editor {
+    /// The map with the voxel edits made in the demo, which are saved with it unchanged.
+    file: MapFile,
+    path: PathBuf,
+    /// Parameters and size of the next map generated.
+    params: GenParams,
+    size: UVec2,
+    tool: Tool,
+    /// Columns this far from the one under the cursor are changed as well.
+    radius: u32,
+    /// What the paint tool puts on columns.
+    surface: NodeType,
+    layers: [bool; Layer::ALL.len()],
+    /// The column under the cursor.
+    hover: Option<IVec2>,
+    status: String,
+    /// The map changed since it was drawn.
+    redraw: bool,
+}
+
+impl Editor {
+    /// The editor for a map file, with a new map if the file doesn't exist.
+    fn open(path: PathBuf) -> Result<Self, String> {
+        let mut editor = Self {
+            file: MapFile {
+                map: Map::default(),
+                seed: None,
+                voxels: Vec::new(),
+                water: Vec::new(),
+            },
+            path,
+            params: GenParams::default(),
+            size: UVec2::new(200, 200),
+            tool: Tool::Raise,
+            radius: 2,
+            surface: NodeType::Grass,
+            layers: [true, true, false, true, false],
+            hover: None,
+            status: String::new(),
+            redraw: true,
+        };
+        if editor.path.exists() {
+            editor.load()?;
+        } else {
+            editor.generate();
+            editor.status = format!("New map, not saved to {} yet", editor.path.display());
+        }
+        Ok(editor)
+    }
+
+    fn selected(&self, button: EditorButton) -> bool {
+        match button {
+            EditorButton::Tool(tool) => self.tool == tool,
+            EditorButton::Surface(surface) => self.surface == surface,
+            EditorButton::Layer(layer) => self.layers[layer as usize],
+            _ => false,
+        }
+    }
+
+    fn press(&mut self, button: EditorButton) {
+        match button {
+            EditorButton::Step(param, steps) => param.step(&mut self.params, &mut self.size, steps),
+            EditorButton::Generate => self.generate(),
+            EditorButton::Tool(tool) => self.tool = tool,
+            EditorButton::Radius(steps) => {
+                self.radius = self.radius.saturating_add_signed(steps).min(MAX_RADIUS)
+            }
+            EditorButton::Surface(surface) => self.surface = surface,
+            EditorButton::Layer(layer) => {
+                self.layers[layer as usize] = !self.layers[layer as usize];
+                self.redraw = true;
+            }
+            EditorButton::Save => self.save(),
+            EditorButton::Load => {
+                if let Err(err) = self.load() {
+                    self.status = err;
+                }
+            }
+        }
+    }
+
+    /// Replace the map with a new one from the parameters, dropping the voxel edits of the old.
+    fn generate(&mut self) {
+        let map = Map::generate(Size::new(self.size.x, self.size.y), &self.params);
+        self.file = MapFile {
+            map,
+            seed: Some(self.params.seed),
+            voxels: Vec::new(),
+            water: Vec::new(),
+        };
+        self.status = format!(
+            "Generated a {}x{} map from seed {}",
+            self.size.x, self.size.y, self.params.seed
+        );
+        self.redraw = true;
+    }
+
+    fn save(&mut self) {
+        self.status = match self.file.save(&self.path) {
+            Ok(()) => format!("Saved {}", self.path.display()),
+            Err(err) => err,
+        };
+    }
+
+    fn load(&mut self) -> Result<(), String> {
+        self.file = MapFile::load(&self.path)?;
+        self.size = self.file.map.extent();
+        if let Some(seed) = self.file.seed {
+            self.params.seed = seed;
+        }
+        self.status = format!("Loaded {}", self.path.display());
+        self.redraw = true;
+        Ok(())
+    }
+}
+
+/// Change the columns within `radius` of `center` with a tool. Smoothing averages the heights
+/// around each column from before any of them changed.
+fn apply_tool(map: &mut Map, tool: Tool, center: IVec2, radius: u32, surface: NodeType) {
+    let radius = radius as i32;
+    let changes: Vec<(IVec3, MapNode)> = (-radius..=radius)
+        .flat_map(|x| (-radius..=radius).map(move |z| IVec2::new(x, z)))
+        .filter(|offset| offset.length_squared() <= radius * radius)
+        .filter_map(|offset| {
+            let column = center + offset;
+            let pos = IVec3::new(column.x, 0, column.y);
+            let node = map.get(pos)?;
+            let changed = match tool {
+                Tool::Raise => MapNode {
+                    height: node.height.saturating_add(1),
+                    ..node
+                },
+                Tool::Lower => MapNode {
+                    height: node.height.saturating_sub(1),
+                    ..node
+                },
+                Tool::Paint => MapNode {
+                    surface_type: surface,
+                    ..node
+                },
+                Tool::Smooth => MapNode {
+                    height: smoothed(map, column),
+                    ..node
+                },
+            };
+            Some((pos, changed))
+        })
+        .collect();
+    for (pos, node) in changes {
+        map.set_height(pos, node.height);
+        map.set_surface(pos, node.surface_type);
+    }
+}
+
+/// The average height of a column and the columns around it.
+fn smoothed(map: &Map, column: IVec2) -> i8 {
+    let heights: Vec<i32> = (-1..=1)
+        .flat_map(|x| (-1..=1).map(move |z| column + IVec2::new(x, z)))
+        .filter_map(|around| map.get(IVec3::new(around.x, 0, around.y)))
+        .map(|node| node.height as i32)
+        .collect();
+    (heights.iter().sum::<i32>() as f32 / heights.len() as f32).round() as i8
+}
+
+/// The map from above with the visible layers, a pixel per column and a row of pixels for
+/// every z.
+fn render(map: &Map, layers: &[bool; Layer::ALL.len()]) -> Vec<u8> {
+    let visible = |layer: Layer| layers[layer as usize];
+    let (min, extent) = (map.min_corner(), map.extent());
+    let height_at = |column: IVec2| {
+        map.get(IVec3::new(column.x, 0, column.y))
+            .map(|node| node.height as i32)
+    };
+    let mut pixels = Vec::with_capacity((extent.x * extent.y * 4) as usize);
+    for z in 0..extent.y as i32 {
+        for x in 0..extent.x as i32 {
+            let column = min + IVec2::new(x, z);
+            let node = map
+                .get(IVec3::new(column.x, 0, column.y))
+                .unwrap_or_default();
+            let height = node.height as i32;
+            let water = node.surface_type == NodeType::Water;
+            let mut color = match node.surface_type.block() {
+                block if water && visible(Layer::Water) => block.map_color(),
+                block if !water && visible(Layer::Surface) => block.map_color(),
+                _ => GROUND_COLOR,
+            }
+            .map(|c| c as f32);
+            if visible(Layer::Shading) {
+                let slope = height - height_at(column - IVec2::ONE).unwrap_or(height);
+                let shade = map_shade(height, slope);
+                color = color.map(|c| c * shade);
+            }
+            if visible(Layer::Moisture) {
+                let wet = node.moisture as f32 / 255.0 * 0.6;
+                for (c, w) in color.iter_mut().zip(WET_COLOR) {
+                    *c += (w as f32 - *c) * wet;
+                }
+            }
+            let band = height.div_euclid(CONTOUR_STEP);
+            let contour = [IVec2::X, IVec2::Y].into_iter().any(|next| {
+                height_at(column + next).is_some_and(|h| h.div_euclid(CONTOUR_STEP) != band)
+            });
+            if visible(Layer::Contours) && contour {
+                color = CONTOUR_COLOR.map(|c| c as f32);
+            }
+            let [r, g, b] = color.map(|c| c.clamp(0.0, 255.0) as u8);
+            pixels.extend([r, g, b, 255]);
+        }
+    }
+    pixels
+}
+
+#[derive(Component)]
+struct MapView;
+
+/// Text showing a part of the editor's state.
+#[derive(Component)]
+enum EditorLabel {
+    Param(Param),
+    Radius,
+    Status,
+}
+
+/// The map file given on the command line, or the default one.
+fn map_path(args: &[String]) -> Result<PathBuf, String> {
+    if let Some(flag) = args.iter().find(|arg| arg.starts_with('-')) {
+        return Err(format!("unknown option '{}', see --help", flag));
+    }
+    match args {
+        [] => Ok(Path::new(MAPS_DIR)
+            .join("editor")
+            .with_extension(MAP_EXTENSION)),
+        [arg] => Ok(PathBuf::from(arg)),
+        [_, extra, ..] => Err(format!("unexpected argument '{}', see --help", extra)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if options::wants_help(&args) {
+        print!("{}", USAGE);
+        return;
+    }
+    let editor = map_path(&args)
+        .and_then(Editor::open)
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        });
+
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Terrain editor".into(),
+                resolution: (1280.0, 800.0).into(),
+                ..default()
+            }),
+            ..default()
+        }))
+        .insert_resource(editor)
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                press_buttons,
+                editor_keys,
+                use_tool,
+                update_view,
+                update_labels,
+                style_buttons,
+            )
+                .chain(),
+        )
+        .run();
+}
+
+fn text(parent: &mut ChildBuilder, value: impl Into<String>, size: f32) {
+    parent.spawn((
+        Text::new(value),
+        TextFont {
+            font_size: size,
+            ..default()
+        },
+    ));
+}
+
+fn button(parent: &mut ChildBuilder, label: &str, action: EditorButton) {
+    parent
+        .spawn((
+            Button,
+            action,
+            Node {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(3.0)),
+                border: UiRect::all(Val::Px(2.0)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(BUTTON_COLOR),
+            BorderColor(Color::NONE),
+        ))
+        .with_children(|button| {
+            let color = match action {
+                // Palette buttons have the color of their block, dark text reads on all of them
+                EditorButton::Surface(_) => Color::BLACK,
+                _ => Color::WHITE,
+            };
+            button.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(color),
+            ));
+        });
+}
+
+/// A row of buttons, wrapping when they don't fit.
+fn row(parent: &mut ChildBuilder, buttons: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(4.0),
+            row_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(buttons);
+}
+
+fn panel(parent: &mut ChildBuilder, contents: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn((
+            Node {
+                width: Val::Px(250.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            BackgroundColor(PANEL_COLOR),
+        ))
+        .with_children(contents);
+}
+
+fn setup(mut commands: Commands, mut editor: ResMut<Editor>, mut images: ResMut<Assets<Image>>) {
+    commands.spawn(Camera2d);
+
+    let extent = editor.file.map.extent();
+    let mut image = Image::new_fill(
+        editor.file.map.image_size(),
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+    editor.redraw = true;
+
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Row,
+            ..default()
+        })
+        .with_children(|root| {
+            panel(root, |panel| {
+                text(panel, "Generate", 18.0);
+                for param in Param::ALL {
+                    panel
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(4.0),
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn((
+                                Text::new(format!("{:?}", param)),
+                                TextFont {
+                                    font_size: 14.0,
+                                    ..default()
+                                },
+                                Node {
+                                    width: Val::Px(90.0),
+                                    ..default()
+                                },
+                            ));
+                            row.spawn((
+                                EditorLabel::Param(param),
+                                Text::default(),
+                                TextFont {
+                                    font_size: 14.0,
+                                    ..default()
+                                },
+                                Node {
+                                    width: Val::Px(80.0),
+                                    ..default()
+                                },
+                            ));
+                            button(row, "-", EditorButton::Step(param, -1));
+                            button(row, "+", EditorButton::Step(param, 1));
+                        });
+                }
+                button(panel, "Generate map", EditorButton::Generate);
+            });
+
+            root.spawn((
+                Node {
+                    flex_grow: 1.0,
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.05, 0.05, 0.06)),
+            ))
+            .with_children(|center| {
+                center.spawn((
+                    MapView,
+                    ImageNode::new(image),
+                    RelativeCursorPosition::default(),
+                    Node {
+                        height: Val::Percent(100.0),
+                        max_width: Val::Percent(100.0),
+                        aspect_ratio: Some(extent.x as f32 / extent.y as f32),
+                        ..default()
+                    },
+                ));
+            });
+
+            panel(root, |panel| {
+                text(panel, "Tools", 18.0);
+                row(panel, |row| {
+                    for tool in Tool::ALL {
+                        button(row, &format!("{:?}", tool), EditorButton::Tool(tool));
+                    }
+                });
+                row(panel, |row| {
+                    row.spawn((
+                        EditorLabel::Radius,
+                        Text::default(),
+                        TextFont {
+                            font_size: 14.0,
+                            ..default()
+                        },
+                    ));
+                    button(row, "-", EditorButton::Radius(-1));
+                    button(row, "+", EditorButton::Radius(1));
+                });
+                text(panel, "Palette", 18.0);
+                row(panel, |row| {
+                    for surface in NodeType::ALL {
+                        button(
+                            row,
+                            &format!("{:?}", surface),
+                            EditorButton::Surface(surface),
+                        );
+                    }
+                });
+                text(panel, "Layers", 18.0);
+                row(panel, |row| {
+                    for layer in Layer::ALL {
+                        button(row, &format!("{:?}", layer), EditorButton::Layer(layer));
+                    }
+                });
+                text(panel, "File", 18.0);
+                text(panel, editor.path.display().to_string(), 14.0);
+                row(panel, |row| {
+                    button(row, "Save", EditorButton::Save);
+                    button(row, "Load", EditorButton::Load);
+                });
+                panel.spawn((
+                    EditorLabel::Status,
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                ));
+            });
+        });
+}
+
+fn press_buttons(
+    mut editor: ResMut<Editor>,
+    buttons: Query<(&Interaction, &EditorButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            editor.press(*button);
+        }
+    }
+}
+
+/// 1 to 4 pick a tool, [ and ] change the radius, Ctrl+S saves and Ctrl+O loads the map.
+fn editor_keys(keys: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>) {
+    let tool_keys = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+    ];
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    for (key, tool) in tool_keys.into_iter().zip(Tool::ALL) {
+        if keys.just_pressed(key) {
+            editor.press(EditorButton::Tool(tool));
+        }
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        editor.press(EditorButton::Radius(-1));
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        editor.press(EditorButton::Radius(1));
+    }
+    if ctrl && keys.just_pressed(KeyCode::KeyS) {
+        editor.press(EditorButton::Save);
+    }
+    if ctrl && keys.just_pressed(KeyCode::KeyO) {
+        editor.press(EditorButton::Load);
+    }
+}
+
+/// Use the tool on the column under the cursor while the left mouse button is held.
+fn use_tool(
+    mut editor: ResMut<Editor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    mut since_used: Local<f32>,
+    views: Query<&RelativeCursorPosition, With<MapView>>,
+) {
+    let hover = views
+        .iter()
+        .find(|cursor| cursor.mouse_over())
+        .and_then(|cursor| cursor.normalized)
+        .map(|normalized| editor.file.map.column_at(normalized));
+    if editor.hover != hover {
+        editor.hover = hover;
+    }
+    let Some(column) = hover else {
+        return;
+    };
+    if !mouse.pressed(MouseButton::Left) {
+        return;
+    }
+    *since_used += time.delta_secs();
+    if !mouse.just_pressed(MouseButton::Left) && *since_used < STROKE_INTERVAL {
+        return;
+    }
+    *since_used = 0.0;
+    let Editor {
+        file,
+        tool,
+        radius,
+        surface,
+        ..
+    } = &mut *editor;
+    apply_tool(&mut file.map, *tool, column, *radius, *surface);
+    editor.redraw = true;
+}
+
+fn update_view(
+    mut editor: ResMut<Editor>,
+    mut images: ResMut<Assets<Image>>,
+    mut views: Query<(&ImageNode, &mut Node), With<MapView>>,
+) {
+    if !editor.redraw {
+        return;
+    }
+    editor.redraw = false;
+    let extent = editor.file.map.extent();
+    for (view, mut node) in views.iter_mut() {
+        if let Some(image) = images.get_mut(&view.image) {
+            image.resize(editor.file.map.image_size());
+            image.data = render(&editor.file.map, &editor.layers);
+        }
+        node.aspect_ratio = Some(extent.x as f32 / extent.y as f32);
+    }
+}
+
+fn update_labels(editor: Res<Editor>, mut labels: Query<(&mut Text, &EditorLabel)>) {
+    if !editor.is_changed() {
+        return;
+    }
+    let hovered = editor.hover.and_then(|column| {
+        let node = editor.file.map.get(IVec3::new(column.x, 0, column.y))?;
+        Some(format!(
+            "{} {}: {:?} height {} moisture {}",
+            column.x, column.y, node.surface_type, node.height, node.moisture
+        ))
+    });
+    for (mut text, label) in labels.iter_mut() {
+        text.0 = match label {
+            EditorLabel::Param(param) => param.value(&editor.params, editor.size),
+            EditorLabel::Radius => format!("Radius {}", editor.radius),
+            EditorLabel::Status => {
+                format!("{}\n{}", editor.status, hovered.clone().unwrap_or_default())
+            }
+        };
+    }
+}
+
+/// Selected buttons get a white border, hovered ones a grey one.
+#[allow(clippy::type_complexity)]
+fn style_buttons(
+    editor: Res<Editor>,
+    mut buttons: Query<(
+        &Interaction,
+        &EditorButton,
+        &mut BackgroundColor,
+        &mut BorderColor,
+    )>,
+) {
+    for (interaction, button, mut background, mut border) in buttons.iter_mut() {
+        background.0 = match button {
+            EditorButton::Surface(surface) => {
+                let [r, g, b] = surface.block().map_color();
+                Color::srgb_u8(r, g, b)
+            }
+            _ => BUTTON_COLOR,
+        };
+        border.0 = match interaction {
+            _ if editor.selected(*button) => SELECTED_BORDER,
+            Interaction::Hovered | Interaction::Pressed => HOVERED_BORDER,
+            Interaction::None => Color::NONE,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use voxel_demo::BlockTexture;
+
+    fn height(map: &Map, x: i32, z: i32) -> i8 {
+        map.get(IVec3::new(x, 0, z)).unwrap().height
+    }
+
+    /// A 5x5 map from -2 to 2, each column 4 higher than its x plus z.
+    fn slope() -> Map {
+        let rows = (-2..=2)
+            .map(|x| {
+                (-2..=2)
+                    .map(|z| MapNode {
+                        surface_type: NodeType::Grass,
+                        height: x + z + 4,
+                        moisture: 0,
+                    })
+                    .collect()
+            })
+            .collect();
+        Map::from_rows(rows).unwrap()
+    }
+
+    #[test]
+    fn tools_change_columns_in_radius() {
+        let mut sut = slope();
+        apply_tool(&mut sut, Tool::Raise, IVec2::ZERO, 1, NodeType::Grass);
+        // The corners of the radius are left alone
+        assert_eq!(height(&sut, 0, 0), 5);
+        assert_eq!(height(&sut, 1, 0), 6);
+        assert_eq!(height(&sut, 1, 1), 6);
+
+        apply_tool(&mut sut, Tool::Paint, IVec2::new(-2, -2), 0, NodeType::Sand);
+        let corner = sut.get(IVec3::new(-2, 0, -2)).unwrap();
+        assert_eq!((corner.surface_type, corner.height), (NodeType::Sand, 0));
+        // Columns outside the map are skipped
+        apply_tool(
+            &mut sut,
+            Tool::Lower,
+            IVec2::new(-3, -3),
+            1,
+            NodeType::Grass,
+        );
+        assert_eq!(height(&sut, -2, -2), 0);
+    }
+
+    #[test]
+    fn smooth_averages_neighbours() {
+        let mut sut = Map::from_rows(vec![vec![MapNode::default(); 3]; 3]).unwrap();
+        sut.set_height(IVec3::ZERO, 9);
+        apply_tool(&mut sut, Tool::Smooth, IVec2::ZERO, 1, NodeType::Grass);
+        assert_eq!(height(&sut, 0, 0), 1);
+        // Edges average the columns they have
+        assert_eq!(height(&sut, -1, 0), 2);
+    }
+
+    #[test]
+    fn map_path_from_args() {
+        let args =
+            |args: &[&str]| map_path(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+        assert_eq!(args(&["a.map"]), Ok(PathBuf::from("a.map")));
+        assert_eq!(args(&[]), Ok(PathBuf::from("assets/maps/editor.map")));
+        assert_eq!(
+            args(&["a.map", "--size"]),
+            Err("unknown option '--size', see --help".into())
+        );
+        assert_eq!(
+            args(&["a.map", "b.map"]),
+            Err("unexpected argument 'b.map', see --help".into())
+        );
+    }
+
+    #[test]
+    fn params_stay_in_range() {
+        let (mut params, mut size) = (GenParams::default(), UVec2::new(20, MAX_MAP_SIZE - 4));
+        Param::Width.step(&mut params, &mut size, -1);
+        Param::Depth.step(&mut params, &mut size, 1);
+        assert_eq!(size, UVec2::new(SIZE_STEP, MAX_MAP_SIZE));
+        Param::Octaves.step(&mut params, &mut size, -10);
+        assert_eq!(params.octaves, 1);
+        Param::Frequency.step(&mut params, &mut size, 3);
+        assert_eq!(Param::Frequency.value(&params, size), "1.40");
+        Param::Persistence.step(&mut params, &mut size, -20);
+        assert_eq!(params.persistence, 0.05);
+        params.seed = 0;
+        Param::Seed.step(&mut params, &mut size, -1);
+        assert_eq!(params.seed, u32::MAX);
+    }
+
+    #[test]
+    fn render_layers() {
+        let mut map = Map::from_rows(vec![vec![MapNode::default(); 2]; 2]).unwrap();
+        map.set_surface(IVec3::new(0, 0, 0), NodeType::Water);
+        let layers = |visible: [Layer; 2]| Layer::ALL.map(|layer| visible.contains(&layer));
+        let pixel = |pixels: &[u8], index: usize| pixels[index * 4..index * 4 + 3].to_vec();
+        // Column 0 0 is the last pixel, a row of pixels for every z
+        let sut = render(&map, &layers([Layer::Surface, Layer::Water]));
+        assert_eq!(pixel(&sut, 3), BlockTexture::WaterBrick.map_color());
+        assert_eq!(pixel(&sut, 0), BlockTexture::GrassBrick.map_color());
+        let sut = render(&map, &layers([Layer::Surface, Layer::Surface]));
+        assert_eq!(pixel(&sut, 3), GROUND_COLOR);
+        map.set_height(IVec3::new(-1, 0, -1), 5);
+        let sut = render(&map, &layers([Layer::Contours, Layer::Contours]));
+        assert_eq!(pixel(&sut, 0), CONTOUR_COLOR);
+        assert_eq!(pixel(&sut, 3), GROUND_COLOR);
+    }
+}
@@ -0,0 +1,408 @@
+//! Makes, inspects and converts maps without a window or GPU, for scripts and pipelines.
+
+use bevy::math::{IVec2, IVec3, UVec2};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use voxel_demo::map_file::MAP_EXTENSION;
+use voxel_demo::textures::map_shade;
+use voxel_demo::{heightmap, options, vox, GenParams, Map, MapFile, NodeType, Size};
+
+const USAGE: &str = "\
+Usage: maptool <command> [options]
+
+Commands:
+  generate <file> [map options]   Generate a map into a .map, .png or .vox file
+  info <file>                     Show the size, heights and surfaces of a map
+  convert <from> <to>             Convert between .map files, .png heightmaps and .vox models
+  preview <file> <image.png>      Render a map from above
+      --scale <n>                 Pixels per column, 1 to 16 [default: 4]
+
+Map options:
+  --size <WxD>           Columns of the generated map along x and z [default: 200x200]
+  --seed <n>             Seed of the terrain noise [default: 1234]
+  --octaves <n>          Layers of noise adding detail, 1 to 32 [default: 5]
+  --frequency <f>        How quickly the terrain changes [default: 1.1]
+  --lacunarity <f>       Frequency of each layer relative to the one before [default: 2.8]
+  --persistence <f>      Strength of each layer relative to the one before [default: 0.4]
+  --amplitude <f>        Height of the terrain noise in voxels [default: 50]
+
+Heightmaps store height 0 as grey level 128 and .vox models as voxel z 128. Both only keep
+the heights and surfaces of a map, not its moisture or the voxels edited in the demo.
+
+  -h, --help             Show this help
+";
+
+const MAX_SCALE: u32 = 16;
+/// Previews have at most as many pixels as a square image this wide, about 200 MB of RGB.
+const MAX_PREVIEW_SIZE: u64 = 8192;
+const DEFAULT_SCALE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Map,
+    Heightmap,
+    Vox,
+}
+
+impl Format {
+    fn of(file: &Path) -> Result<Self, String> {
+        match file.extension().and_then(|extension| extension.to_str()) {
+            Some(MAP_EXTENSION) => Ok(Format::Map),
+            Some("png") => Ok(Format::Heightmap),
+            Some("vox") => Ok(Format::Vox),
+            _ => Err(format!(
+                "{}: unknown map format, expected a .map, .png or .vox file",
+                file.display()
+            )),
+        }
+    }
+}
+
+fn load(file: &Path) -> Result<MapFile, String> {
+    let in_file = |err: String| format!("{}: {}", file.display(), err);
+    let map = match Format::of(file)? {
+        Format::Map => return MapFile::load(file),
+        Format::Heightmap => {
+            let (extent, grey) = heightmap::read_png(file)?;
+            heightmap::decode(extent, &grey).map_err(in_file)?
+        }
+        Format::Vox => {
+            let bytes =
+                fs::read(file).map_err(|err| format!("can't read {}: {}", file.display(), err))?;
+            vox::decode(&bytes).map_err(in_file)?
+        }
+    };
+    Ok(MapFile {
+        map,
+        seed: None,
+        voxels: Vec::new(),
+        water: Vec::new(),
+    })
+}
+
+fn save(map_file: &MapFile, file: &Path) -> Result<(), String> {
+    match Format::of(file)? {
+        Format::Map => map_file.save(file),
+        Format::Heightmap => heightmap::write_png(
+            file,
+            map_file.map.extent(),
+            png::ColorType::Grayscale,
+            &heightmap::encode(&map_file.map),
+        ),
+        Format::Vox => {
+            let bytes =
+                vox::encode(&map_file.map).map_err(|err| format!("{}: {}", file.display(), err))?;
+            file.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(file, bytes))
+                .map_err(|err| format!("can't write {}: {}", file.display(), err))
+        }
+    }
+}
+
+/// The files a command was given, when it was given `N` of them.
+fn files<const N: usize>(command: &str, args: &[String]) -> Result<[PathBuf; N], String> {
+    let files: Vec<PathBuf> = args.iter().map(PathBuf::from).collect();
+    files.try_into().map_err(|files: Vec<PathBuf>| {
+        format!(
+            "{} takes {} file{}, got {}, see --help",
+            command,
+            N,
+            if N == 1 { "" } else { "s" },
+            files.len()
+        )
+    })
+}
+
+fn unknown_option(flag: &str) -> String {
+    format!("unknown option '{}', see --help", flag)
+}
+
+/// Run a command, returning what it prints.
+fn run(args: &[String]) -> Result<String, String> {
+    let Some((command, args)) = args.split_first() else {
+        return Err("missing command, see --help".into());
+    };
+    if options::wants_help(std::slice::from_ref(command)) || options::wants_help(args) {
+        return Ok(USAGE.into());
+    }
+    let (paths, flags) = options::split_args(args)?;
+    match command.as_str() {
+        "generate" => {
+            let [file] = files(command, &paths)?;
+            Format::of(&file)?;
+            let (mut size, mut params) = (Size::new(200, 200), GenParams::default());
+            for (flag, value) in &flags {
+                if !options::set_map_flag(flag, value, &mut size, &mut params)? {
+                    return Err(unknown_option(flag));
+                }
+            }
+            let map = Map::generate(size, &params);
+            let extent = map.extent();
+            let map_file = MapFile {
+                map,
+                seed: Some(params.seed),
+                voxels: Vec::new(),
+                water: Vec::new(),
+            };
+            save(&map_file, &file)?;
+            Ok(format!(
+                "Generated a {}x{} map from seed {} into {}\n",
+                extent.x,
+                extent.y,
+                params.seed,
+                file.display()
+            ))
+        }
+        "info" => {
+            let [file] = files(command, &paths)?;
+            if let Some((flag, _)) = flags.first() {
+                return Err(unknown_option(flag));
+            }
+            Ok(info(&load(&file)?))
+        }
+        "convert" => {
+            let [from, to] = files(command, &paths)?;
+            if let Some((flag, _)) = flags.first() {
+                return Err(unknown_option(flag));
+            }
+            let map_file = load(&from)?;
+            save(&map_file, &to)?;
+            let mut output = format!("Converted {} to {}\n", from.display(), to.display());
+            let edits = map_file.voxels.len() + map_file.water.len();
+            if edits > 0 && Format::of(&to)? != Format::Map {
+                let _ = writeln!(output, "The {} edited voxels were left out", edits);
+            }
+            Ok(output)
+        }
+        "preview" => {
+            let [from, to] = files(command, &paths)?;
+            if Format::of(&to)? != Format::Heightmap {
+                return Err(format!("{}: previews are .png images", to.display()));
+            }
+            let mut scale = DEFAULT_SCALE;
+            for (flag, value) in &flags {
+                if flag != "--scale" {
+                    return Err(unknown_option(flag));
+                }
+                scale = match value.parse() {
+                    Ok(scale) if (1..=MAX_SCALE).contains(&scale) => scale,
+                    _ => {
+                        return Err(format!(
+                            "invalid value '{}' for --scale: expected 1 to {}",
+                            value, MAX_SCALE
+                        ))
+                    }
+                };
+            }
+            let map = load(&from)?.map;
+            let extent = preview_extent(map.extent(), scale)?;
+            heightmap::write_png(&to, extent, png::ColorType::Rgb, &preview(&map, scale))?;
+            Ok(format!(
+                "Rendered {} to {}, {}x{} pixels\n",
+                from.display(),
+                to.display(),
+                extent.x,
+                extent.y
+            ))
+        }
+        _ => Err(format!("unknown command '{}', see --help", command)),
+    }
+}
+
+/// The size, heights, edits and a histogram of the surfaces of a map.
+fn info(map_file: &MapFile) -> String {
+    let map = &map_file.map;
+    let (min, extent) = (map.min_corner(), map.extent());
+    let max = min + extent.as_ivec2() - 1;
+    let heights: Vec<i32> = map
+        .rows()
+        .iter()
+        .flatten()
+        .map(|n| n.height as i32)
+        .collect();
+    let columns = heights.len();
+    let mean = heights.iter().sum::<i32>() as f32 / columns as f32;
+
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "Size      {}x{} columns, from {} {} to {} {}",
+        extent.x, extent.y, min.x, min.y, max.x, max.y
+    );
+    let seed = map_file.seed.map_or("none".into(), |seed| seed.to_string());
+    let _ = writeln!(text, "Seed      {}", seed);
+    let _ = writeln!(
+        text,
+        "Heights   lowest {}, highest {}, mean {:.1}",
+        heights.iter().min().unwrap_or(&0),
+        heights.iter().max().unwrap_or(&0),
+        mean
+    );
+    let _ = writeln!(
+        text,
+        "Edits     {} voxels, {} water",
+        map_file.voxels.len(),
+        map_file.water.len()
+    );
+    text.push_str("Surfaces\n");
+    for surface in NodeType::ALL {
+        let count = map
+            .rows()
+            .iter()
+            .flatten()
+            .filter(|node| node.surface_type == surface)
+            .count();
+        let share = count as f32 * 100.0 / columns as f32;
+        let _ = writeln!(
+            text,
+            "  {:<8}{:>8}  {:5.1}%",
+            format!("{:?}", surface),
+            count,
+            share
+        );
+    }
+    text
+}
+
+/// Pixels of the preview of a map with `extent` columns, refusing images too large to allocate.
+fn preview_extent(extent: UVec2, scale: u32) -> Result<UVec2, String> {
+    let pixels = extent.as_u64vec2() * scale as u64;
+    if pixels.x * pixels.y > MAX_PREVIEW_SIZE * MAX_PREVIEW_SIZE {
+        return Err(format!(
+            "a {0}x{1} preview has more pixels than {2}x{2}, use a smaller --scale",
+            pixels.x, pixels.y, MAX_PREVIEW_SIZE
+        ));
+    }
+    Ok(extent * scale)
+}
+
+/// The map from above as RGB pixels, `scale` pixels wide for every column. Columns are brighter
+/// the higher they are and lit from the north west.
+fn preview(map: &Map, scale: u32) -> Vec<u8> {
+    let (min, extent) = (map.min_corner(), map.extent());
+    let height_at = |column: IVec2| {
+        map.get(IVec3::new(column.x, 0, column.y))
+            .map(|node| node.height as i32)
+    };
+    let mut pixels =
+        Vec::with_capacity((extent.x * scale) as usize * (extent.y * scale) as usize * 3);
+    for z in 0..(extent.y * scale) as i32 {
+        for x in 0..(extent.x * scale) as i32 {
+            let column = min + IVec2::new(x, z) / scale as i32;
+            let node = map
+                .get(IVec3::new(column.x, 0, column.y))
+                .unwrap_or_default();
+            let height = node.height as i32;
+            let slope = height - height_at(column - IVec2::ONE).unwrap_or(height);
+            let shade = map_shade(height, slope);
+            let color = node.surface_type.block().map_color();
+            pixels.extend(color.map(|c| (c as f32 * shade).clamp(0.0, 255.0) as u8));
+        }
+    }
+    pixels
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => print!("{}", output),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use voxel_demo::MapNode;
+
+    fn run(args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        super::run(&args)
+    }
+
+    /// A 4x3 map of grass at height 2, with a snow peak in the first column and sand in the last.
+    fn test_map() -> Map {
+        let node = |surface_type, height| MapNode {
+            surface_type,
+            height,
+            moisture: 0,
+        };
+        let mut rows = vec![vec![node(NodeType::Grass, 2); 3]; 4];
+        rows[0][0] = node(NodeType::Snow, 8);
+        rows[3][2] = node(NodeType::Sand, 1);
+        Map::from_rows(rows).unwrap()
+    }
+
+    #[test]
+    fn info_lists_surfaces() {
+        let sut = info(&MapFile {
+            map: test_map(),
+            seed: Some(7),
+            voxels: Vec::new(),
+            water: Vec::new(),
+        });
+        let lines: Vec<&str> = sut.lines().collect();
+        assert_eq!(lines[0], "Size      4x3 columns, from -2 -1 to 1 1");
+        assert_eq!(lines[1], "Seed      7");
+        assert!(lines[2].starts_with("Heights   lowest 1, highest 8, mean "));
+        assert_eq!(lines[5], "  Grass         10   83.3%");
+        assert_eq!(lines.len(), 5 + NodeType::ALL.len());
+    }
+
+    #[test]
+    fn preview_scales_columns() {
+        let sut = preview(&test_map(), 2);
+        assert_eq!(sut.len(), 4 * 3 * 4 * 3);
+        // The top left column is snow, two pixels wide and two high
+        let row = 4 * 2 * 3;
+        assert_eq!(sut[0..3], sut[3..6]);
+        assert_eq!(sut[0..3], sut[row..row + 3]);
+        assert_ne!(sut[0..3], sut[6..9]);
+    }
+
+    #[test]
+    fn preview_size_is_capped() {
+        assert_eq!(
+            preview_extent(UVec2::new(4096, 1024), 4),
+            Ok(UVec2::new(16384, 4096))
+        );
+        assert_eq!(
+            preview_extent(UVec2::splat(4096), 16),
+            Err(
+                "a 65536x65536 preview has more pixels than 8192x8192, use a smaller --scale"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn command_errors() {
+        assert_eq!(run(""), Err("missing command, see --help".into()));
+        assert_eq!(
+            run("render a.map"),
+            Err("unknown command 'render', see --help".into())
+        );
+        assert_eq!(
+            run("convert a.map"),
+            Err("convert takes 2 files, got 1, see --help".into())
+        );
+        assert_eq!(
+            run("generate a.map --scale 2"),
+            Err("unknown option '--scale', see --help".into())
+        );
+        assert_eq!(
+            run("generate a.txt --seed 2"),
+            Err("a.txt: unknown map format, expected a .map, .png or .vox file".into())
+        );
+        assert_eq!(
+            run("preview a.map b.png --scale 0"),
+            Err("invalid value '0' for --scale: expected 1 to 16".into())
+        );
+        assert_eq!(run("info --help"), Ok(USAGE.into()));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::textures::BlockTexture;
+use bevy_voxel_world::prelude::WorldVoxel;
+
+/// Light level of the brightest emissive blocks, light drops by one for every voxel it travels.
+pub(crate) const MAX_LIGHT: u8 = 15;
+
+/// Physical and rendering properties shared by every voxel of a block type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BlockProperties {
+    /// Blocks movement and stops cursor raycasts.
+    pub(crate) collidable: bool,
+    /// Rendered by the water world and can be placed into.
+    pub(crate) liquid: bool,
+    /// Block light passes through this block. Only lighting reads it, `bevy_voxel_world` still
+    /// culls the faces between this and neighbouring solid blocks.
+    pub(crate) transparent: bool,
+    /// Tiles are tinted by the moisture of the column, like grass and leaves.
+    pub(crate) tinted: bool,
+    /// Block light level emitted, 0 for non-emissive blocks.
+    pub(crate) emissive: u8,
+    /// Relative time the remove action is held to break the block, stone is 1.0.
+    pub(crate) hardness: f32,
+    /// How quickly the first person player speeds up and stops when walking on the block.
+    pub(crate) friction: f32,
+}
+
+impl BlockProperties {
+    const fn solid(hardness: f32, friction: f32) -> Self {
+        Self {
+            collidable: true,
+            liquid: false,
+            transparent: false,
+            tinted: false,
+            emissive: 0,
+            hardness,
+            friction,
+        }
+    }
+
+    /// Solid blocks drawn with see-through tiles, like fences and glass.
+    const fn cutout(hardness: f32, friction: f32) -> Self {
+        Self {
+            transparent: true,
+            ..Self::solid(hardness, friction)
+        }
+    }
+
+    const fn emissive(self, level: u8) -> Self {
+        Self {
+            emissive: level,
+            ..self
+        }
+    }
+
+    const fn tinted(self) -> Self {
+        Self {
+            tinted: true,
+            ..self
+        }
+    }
+
+    const LIQUID: Self = Self {
+        collidable: false,
+        liquid: true,
+        transparent: true,
+        tinted: false,
+        emissive: 0,
+        hardness: 0.0,
+        friction: 0.0,
+    };
+
+    /// Properties of empty space, used for `Air` and `Unset` voxels.
+    pub(crate) const AIR: Self = Self {
+        collidable: false,
+        liquid: false,
+        transparent: true,
+        tinted: false,
+        emissive: 0,
+        hardness: 0.0,
+        friction: 0.0,
+    };
+
+    pub(crate) fn of(voxel: WorldVoxel<BlockTexture>) -> Self {
+        match voxel {
+            WorldVoxel::Solid(block) => block.properties(),
+            WorldVoxel::Air | WorldVoxel::Unset => Self::AIR,
+        }
+    }
+
+    /// Something else can be placed where this block is, without removing it first.
+    pub(crate) fn replaceable(&self) -> bool {
+        !self.collidable
+    }
+}
+
+impl BlockTexture {
+    pub(crate) fn properties(&self) -> BlockProperties {
+        match self {
+            BlockTexture::GrassBrick => BlockProperties::solid(0.6, 0.6).tinted(),
+            BlockTexture::SnowyBrick => BlockProperties::solid(0.2, 0.2),
+            BlockTexture::DirtBrick => BlockProperties::solid(0.5, 0.6),
+            BlockTexture::SandBrick => BlockProperties::solid(0.5, 0.5),
+            BlockTexture::GravelBrick => BlockProperties::solid(0.6, 0.7),
+            BlockTexture::StoneBrick => BlockProperties::solid(1.0, 0.6),
+            BlockTexture::RockBrick => BlockProperties::solid(1.5, 0.7),
+            BlockTexture::WaterBrick => BlockProperties::LIQUID,
+            BlockTexture::FullBrick => BlockProperties::solid(1.0, 0.6),
+            BlockTexture::FenceStone | BlockTexture::FenceStonePost => {
+                BlockProperties::cutout(1.0, 0.6)
+            }
+            BlockTexture::FenceWood | BlockTexture::FenceWoodPost => {
+                BlockProperties::cutout(0.5, 0.6)
+            }
+            BlockTexture::GlassFrame | BlockTexture::GlassPane => BlockProperties::cutout(0.3, 0.4),
+            BlockTexture::TrackNorthSouth
+            | BlockTexture::TrackEastWest
+            | BlockTexture::TrackNorthEast
+            | BlockTexture::TrackSouthEast
+            | BlockTexture::TrackSouthWest
+            | BlockTexture::TrackNorthWest => BlockProperties::cutout(0.7, 0.5),
+            BlockTexture::Leaves => BlockProperties::cutout(0.2, 0.4).tinted(),
+            BlockTexture::Lava => BlockProperties::solid(1.0, 0.2).emissive(MAX_LIGHT),
+            BlockTexture::RubyOre | BlockTexture::EmeraldOre => {
+                BlockProperties::solid(1.2, 0.6).emissive(7)
+            }
+            BlockTexture::DiamondOre => BlockProperties::solid(1.5, 0.6).emissive(10),
+        }
+    }
+}
+
+pub(crate) fn is_collidable(voxel: WorldVoxel<BlockTexture>) -> bool {
+    BlockProperties::of(voxel).collidable
+}
+
+pub(crate) fn is_liquid(voxel: WorldVoxel<BlockTexture>) -> bool {
+    BlockProperties::of(voxel).liquid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn properties_of_voxels() {
+        let solid = |block| WorldVoxel::Solid(block);
+        let table = [
+            // (voxel, collidable, liquid, replaceable)
+            (WorldVoxel::Air, false, false, true),
+            (WorldVoxel::Unset, false, false, true),
+            (solid(BlockTexture::WaterBrick), false, true, true),
+            (solid(BlockTexture::StoneBrick), true, false, false),
+            (solid(BlockTexture::FenceWood), true, false, false),
+            (solid(BlockTexture::GlassPane), true, false, false),
+            (solid(BlockTexture::TrackEastWest), true, false, false),
+            (solid(BlockTexture::Lava), true, false, false),
+        ];
+        for (voxel, collidable, liquid, replaceable) in table {
+            assert_eq!(is_collidable(voxel), collidable, "{:?}", voxel);
+            assert_eq!(is_liquid(voxel), liquid, "{:?}", voxel);
+            assert_eq!(
+                BlockProperties::of(voxel).replaceable(),
+                replaceable,
+                "{:?}",
+                voxel
+            );
+        }
+    }
+
+    #[test]
+    fn cutout_blocks_let_light_through() {
+        assert!(BlockTexture::Leaves.properties().transparent);
+        assert!(BlockTexture::GlassFrame.properties().transparent);
+        assert!(!BlockTexture::DirtBrick.properties().transparent);
+        assert!(BlockTexture::GrassBrick.properties().tinted);
+        assert_eq!(BlockTexture::Lava.properties().emissive, MAX_LIGHT);
+    }
+}
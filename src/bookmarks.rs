@@ -0,0 +1,397 @@
+use crate::camera::CameraMode;
+use crate::input::{Action, Actions};
+use crate::player::Player;
+use bevy::prelude::*;
+use smooth_bevy_cameras::LookTransform;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Camera file of the generated map.
+pub(crate) const DEFAULT_CAMERA_FILE: &str = "assets/maps/noise_map.cameras";
+
+/// Where the camera is and what it looks at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Pose {
+    pub(crate) eye: Vec3,
+    pub(crate) target: Vec3,
+}
+
+impl Pose {
+    fn of(look: &LookTransform) -> Self {
+        Self {
+            eye: look.eye,
+            target: look.target,
+        }
+    }
+
+    fn apply(&self, look: &mut LookTransform) {
+        look.eye = self.eye;
+        look.target = self.target;
+    }
+}
+
+/// Catmull-Rom spline through `p1` and `p2` at `u` in 0..1, `p0` and `p3` shape the tangents.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, u: f32) -> Vec3 {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * u
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+}
+
+/// Keyframed camera poses, with the seconds since the path started.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CameraPath {
+    pub(crate) keys: Vec<(f32, Pose)>,
+}
+
+impl CameraPath {
+    pub(crate) fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    /// The pose at `time`, passing smoothly through every keyframe.
+    pub(crate) fn sample(&self, time: f32) -> Option<Pose> {
+        let next = self.keys.iter().position(|(t, _)| *t > time);
+        let (i1, i2) = match next {
+            Some(0) => return self.keys.first().map(|(_, pose)| *pose),
+            Some(next) => (next - 1, next),
+            None => return self.keys.last().map(|(_, pose)| *pose),
+        };
+        let i0 = i1.saturating_sub(1);
+        let i3 = (i2 + 1).min(self.keys.len() - 1);
+        let (t1, k1) = self.keys[i1];
+        let (t2, k2) = self.keys[i2];
+        let (k0, k3) = (self.keys[i0].1, self.keys[i3].1);
+        let u = (time - t1) / (t2 - t1);
+        Some(Pose {
+            eye: catmull_rom(k0.eye, k1.eye, k2.eye, k3.eye, u),
+            target: catmull_rom(k0.target, k1.target, k2.target, k3.target, u),
+        })
+    }
+}
+
+/// A path being recorded or played back, with the time it started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Take {
+    Recording(f32),
+    Playing(usize, f32),
+}
+
+/// Named camera poses and paths of a map, saved to a file next to it.
+///
+/// The file has a line per pose and per path keyframe, keyframes belong to the path above them:
+///
+/// ```text
+/// # pose name eye_x eye_y eye_z target_x target_y target_z
+/// pose overview 10 40 10 0 0 0
+/// path flyover
+/// # key seconds eye_x eye_y eye_z target_x target_y target_z
+/// key 0 10 40 10 0 0 0
+/// ```
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub(crate) struct CameraBookmarks {
+    pub(crate) poses: Vec<(String, Pose)>,
+    pub(crate) paths: Vec<(String, CameraPath)>,
+    file: PathBuf,
+    /// Index of the pose jumped to last.
+    current: Option<usize>,
+    take: Option<Take>,
+}
+
+fn parse_numbers<const N: usize>(words: &[&str]) -> Result<[f32; N], String> {
+    if words.len() != N {
+        return Err(format!("expected {} numbers, found {}", N, words.len()));
+    }
+    let mut numbers = [0.0; N];
+    for (number, word) in numbers.iter_mut().zip(words) {
+        *number = word
+            .parse()
+            .map_err(|_| format!("invalid number '{}'", word))?;
+    }
+    Ok(numbers)
+}
+
+fn parse_pose(words: &[&str]) -> Result<Pose, String> {
+    let [ex, ey, ez, tx, ty, tz] = parse_numbers(words)?;
+    Ok(Pose {
+        eye: Vec3::new(ex, ey, ez),
+        target: Vec3::new(tx, ty, tz),
+    })
+}
+
+impl CameraBookmarks {
+    pub(crate) fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut bookmarks = Self::default();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: String| format!("{}:{}: {}", name, line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["pose", name, pose @ ..] => {
+                    let pose = parse_pose(pose).map_err(error)?;
+                    bookmarks.poses.push((name.to_string(), pose));
+                }
+                ["path", name] => bookmarks
+                    .paths
+                    .push((name.to_string(), CameraPath::default())),
+                ["key", time, pose @ ..] => {
+                    let Some((_, path)) = bookmarks.paths.last_mut() else {
+                        return Err(error("keyframe outside of a path".into()));
+                    };
+                    let [time] = parse_numbers(&[*time]).map_err(error)?;
+                    if time < path.duration() {
+                        return Err(error(format!("keyframe at {} goes back in time", time)));
+                    }
+                    path.keys.push((time, parse_pose(pose).map_err(error)?));
+                }
+                _ => return Err(error(format!("can't parse '{}'", line))),
+            }
+        }
+        Ok(bookmarks)
+    }
+
+    /// The bookmarks in `file`, none if it doesn't exist yet.
+    pub(crate) fn load(file: impl AsRef<Path>) -> Result<Self, String> {
+        let file = file.as_ref();
+        let mut bookmarks = match fs::read_to_string(file) {
+            Ok(source) => Self::parse(&file.display().to_string(), &source)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(format!("can't read {}: {}", file.display(), err)),
+        };
+        bookmarks.file = file.to_path_buf();
+        Ok(bookmarks)
+    }
+
+    pub(crate) fn save(&self) -> Result<(), String> {
+        self.file
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.file, self.to_string()))
+            .map_err(|err| format!("can't write {}: {}", self.file.display(), err))
+    }
+
+    /// Add a pose named after its number.
+    fn add_pose(&mut self, pose: Pose) -> String {
+        let name = format!("pose_{}", self.poses.len() + 1);
+        self.poses.push((name.clone(), pose));
+        self.current = Some(self.poses.len() - 1);
+        name
+    }
+
+    /// The pose after the one jumped to last.
+    fn next_pose(&mut self) -> Option<Pose> {
+        if self.poses.is_empty() {
+            return None;
+        }
+        let next = self
+            .current
+            .map_or(0, |current| (current + 1) % self.poses.len());
+        self.current = Some(next);
+        Some(self.poses[next].1)
+    }
+
+    /// Stop recording the last path, it is dropped when it has no keyframes. Returns whether a
+    /// path was recorded.
+    fn stop_recording(&mut self) -> bool {
+        if !matches!(self.take, Some(Take::Recording(_))) {
+            return false;
+        }
+        self.take = None;
+        let (name, path) = self.paths.last().expect("recording path");
+        if path.keys.is_empty() {
+            self.paths.pop();
+            false
+        } else {
+            info!("Recorded {} with {} keyframes", name, path.keys.len());
+            true
+        }
+    }
+}
+
+impl fmt::Display for CameraBookmarks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pose = |f: &mut fmt::Formatter<'_>, pose: &Pose| {
+            let (eye, target) = (pose.eye, pose.target);
+            writeln!(
+                f,
+                "{} {} {} {} {} {}",
+                eye.x, eye.y, eye.z, target.x, target.y, target.z
+            )
+        };
+        writeln!(
+            f,
+            "# pose name eye_x eye_y eye_z target_x target_y target_z"
+        )?;
+        for (name, p) in &self.poses {
+            write!(f, "pose {} ", name)?;
+            pose(f, p)?;
+        }
+        for (name, path) in &self.paths {
+            writeln!(f, "path {}", name)?;
+            writeln!(
+                f,
+                "# key seconds eye_x eye_y eye_z target_x target_y target_z"
+            )?;
+            for (time, p) in &path.keys {
+                write!(f, "key {} ", time)?;
+                pose(f, p)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bookmark the camera pose or jump through the bookmarks. Start and stop recording a path and
+/// add keyframes to it, or play back the last path.
+pub(crate) fn camera_bookmark_keys(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut mode: ResMut<CameraMode>,
+    mut cameras: Query<&mut LookTransform, With<Player>>,
+) {
+    let Ok(mut look) = cameras.get_single_mut() else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let mut changed = false;
+
+    if actions.just_pressed(Action::Bookmark) {
+        let name = bookmarks.add_pose(Pose::of(&look));
+        info!("Bookmarked camera as {}", name);
+        changed = true;
+    }
+    if actions.just_pressed(Action::NextBookmark) {
+        if let Some(pose) = bookmarks.next_pose() {
+            *mode = CameraMode::Fly;
+            pose.apply(&mut look);
+        }
+    }
+    if actions.just_pressed(Action::RecordPath) {
+        if let Some(Take::Recording(_)) = bookmarks.take {
+            changed |= bookmarks.stop_recording();
+        } else {
+            let name = format!("path_{}", bookmarks.paths.len() + 1);
+            bookmarks.paths.push((name, CameraPath::default()));
+            bookmarks.take = Some(Take::Recording(now));
+        }
+    }
+    if actions.just_pressed(Action::AddKeyframe) {
+        if let Some(Take::Recording(started)) = bookmarks.take {
+            let pose = Pose::of(&look);
+            let (_, path) = bookmarks.paths.last_mut().expect("recording path");
+            path.keys.push((now - started, pose));
+        }
+    }
+    if actions.just_pressed(Action::PlayPath) {
+        // A path being recorded is finished first, then played
+        changed |= bookmarks.stop_recording();
+        bookmarks.take = match bookmarks.take {
+            Some(Take::Playing(..)) => None,
+            _ if bookmarks.paths.is_empty() => None,
+            _ => {
+                *mode = CameraMode::Fly;
+                Some(Take::Playing(bookmarks.paths.len() - 1, now))
+            }
+        };
+    }
+
+    if changed {
+        if let Err(err) = bookmarks.save() {
+            error!("{}", err);
+        }
+    }
+}
+
+/// Move the camera along the path being played back.
+pub(crate) fn play_camera_path(
+    time: Res<Time>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut cameras: Query<&mut LookTransform, With<Player>>,
+) {
+    let Some(Take::Playing(index, started)) = bookmarks.take else {
+        return;
+    };
+    let elapsed = time.elapsed_secs() - started;
+    let path = &bookmarks.paths[index].1;
+    if let Some(pose) = path.sample(elapsed) {
+        for mut look in cameras.iter_mut() {
+            pose.apply(&mut look);
+        }
+    }
+    if elapsed >= path.duration() {
+        bookmarks.take = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(x: f32) -> Pose {
+        Pose {
+            eye: Vec3::new(x, 10.0, 0.0),
+            target: Vec3::new(x, 0.0, -5.0),
+        }
+    }
+
+    #[test]
+    fn path_passes_through_keyframes() {
+        let sut = CameraPath {
+            keys: vec![(0.0, pose(0.0)), (1.0, pose(4.0)), (3.0, pose(6.0))],
+        };
+        assert_eq!(sut.sample(-1.0), Some(pose(0.0)));
+        assert_eq!(sut.sample(1.0), Some(pose(4.0)));
+        assert_eq!(sut.sample(5.0), Some(pose(6.0)));
+        let between = sut.sample(2.0).unwrap().eye.x;
+        assert!(between > 4.0 && between < 6.0);
+        // No jump when crossing a keyframe
+        let before = sut.sample(0.999).unwrap().eye.x;
+        let after = sut.sample(1.001).unwrap().eye.x;
+        assert!((after - before).abs() < 0.05);
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let mut sut = CameraBookmarks::default();
+        sut.add_pose(pose(1.5));
+        sut.paths.push((
+            "flyover".into(),
+            CameraPath {
+                keys: vec![(0.0, pose(0.0)), (2.5, pose(-3.0))],
+            },
+        ));
+        let parsed = CameraBookmarks::parse("test", &sut.to_string()).unwrap();
+        assert_eq!(parsed.poses, sut.poses);
+        assert_eq!(parsed.paths, sut.paths);
+    }
+
+    #[test]
+    fn stop_recording_keeps_paths_with_keyframes() {
+        let mut sut = CameraBookmarks::default();
+        assert!(!sut.stop_recording());
+        sut.paths.push(("path_1".into(), CameraPath::default()));
+        sut.take = Some(Take::Recording(0.0));
+        assert!(!sut.stop_recording());
+        assert!(sut.paths.is_empty());
+
+        sut.paths.push(("path_1".into(), CameraPath::default()));
+        sut.paths[0].1.keys.push((0.5, pose(1.0)));
+        sut.take = Some(Take::Recording(0.0));
+        assert!(sut.stop_recording());
+        assert_eq!(sut.take, None);
+        assert_eq!(sut.paths.len(), 1);
+    }
+
+    #[test]
+    fn keyframe_needs_path() {
+        let err = CameraBookmarks::parse("test", "key 0 1 2 3 4 5 6").unwrap_err();
+        assert_eq!(err, "test:1: keyframe outside of a path");
+    }
+}
@@ -0,0 +1,423 @@
+use crate::bookmarks::{self, CameraBookmarks};
+use crate::cursor::VoxelTrace;
+use crate::input::{self, Action, Actions};
+use crate::player::{self, Player};
+use crate::selection::Selection;
+use crate::terrain::MyMainWorld;
+use crate::water::WaterWorld;
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use bevy_voxel_world::prelude::*;
+use smooth_bevy_cameras::controllers::orbit::{self, OrbitCameraController, OrbitCameraPlugin};
+use smooth_bevy_cameras::controllers::unreal::{
+    ControlEvent, UnrealCameraBundle, UnrealCameraController, UnrealCameraPlugin,
+};
+use smooth_bevy_cameras::{LookTransform, LookTransformPlugin};
+use std::path::PathBuf;
+
+/// Heights of the RTS camera panned every second.
+const RTS_PAN_SPEED: f32 = 3.0;
+/// Cursor distance from the window edge that scrolls the RTS camera.
+const RTS_EDGE: f32 = 12.0;
+const RTS_MIN_HEIGHT: f32 = 8.0;
+const RTS_MAX_HEIGHT: f32 = 200.0;
+const RTS_ZOOM_STEP: f32 = 0.1;
+/// Horizontal distance of the RTS camera behind its target, relative to its height.
+const RTS_TILT: f32 = 0.6;
+/// How much the orbit camera zooms for every pixel the mouse moves while zooming.
+const ORBIT_DRAG_ZOOM: f32 = 0.01;
+
+/// How the main camera is controlled. All modes move the same camera, so the
+/// `VoxelWorldCamera` markers stay on it and chunks keep spawning around it.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum CameraMode {
+    /// Unreal editor style fly camera.
+    #[default]
+    Fly,
+    /// Orbit around the voxel the cursor was on when switching.
+    Orbit,
+    /// Top down camera that pans over the ground and zooms.
+    Rts,
+    /// Walking as the player.
+    FirstPerson,
+}
+
+impl CameraMode {
+    fn next(&self) -> Self {
+        match self {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Rts,
+            CameraMode::Rts => CameraMode::FirstPerson,
+            CameraMode::FirstPerson => CameraMode::Fly,
+        }
+    }
+
+    /// Whether the camera takes the scroll wheel, otherwise it picks blocks from the hotbar. The
+    /// camera only takes it while one of its buttons is held: fly changes its speed with it,
+    /// orbit and RTS zoom.
+    pub(crate) fn uses_wheel(&self, actions: &Actions) -> bool {
+        let held: &[Action] = match self {
+            CameraMode::Fly => &[Action::Drag, Action::Look, Action::Pan],
+            CameraMode::Orbit => &[Action::Orbit, Action::Pan, Action::Zoom],
+            CameraMode::Rts => &[Action::Pan],
+            CameraMode::FirstPerson => &[],
+        };
+        held.iter().any(|action| actions.pressed(*action))
+    }
+}
+
+/// Eye of the RTS camera looking down at `target` from `height` above it.
+fn rts_eye(target: Vec3, height: f32) -> Vec3 {
+    target + Vec3::new(0.0, height, height * RTS_TILT)
+}
+
+/// Cycle through the camera modes, each starting from where the camera is.
+pub(crate) fn switch_camera_mode(
+    actions: Res<Actions>,
+    mut mode: ResMut<CameraMode>,
+    trace: Res<VoxelTrace>,
+    mut cameras: Query<(&mut LookTransform, Option<&mut Player>)>,
+) {
+    if !actions.just_pressed(Action::SwitchCamera) {
+        return;
+    }
+    *mode = mode.next();
+    info!("Camera mode {:?}", *mode);
+
+    for (mut look, player) in cameras.iter_mut() {
+        match *mode {
+            CameraMode::Fly => {}
+            CameraMode::Orbit => look.target = trace.end,
+            CameraMode::Rts => {
+                let height = look.eye.y.clamp(RTS_MIN_HEIGHT, RTS_MAX_HEIGHT);
+                let direction = look.look_direction().unwrap_or(Vec3::NEG_Z);
+                // Look at the ground ahead of the camera
+                let target = Vec3::new(look.eye.x + direction.x, 0.0, look.eye.z + direction.z);
+                look.target = target;
+                look.eye = rts_eye(target, height);
+            }
+            CameraMode::FirstPerson => {
+                if let Some(mut player) = player {
+                    *player = Player::looking_from(&look);
+                }
+            }
+        }
+    }
+}
+
+/// Hand the input to the controller of the active mode. Fly and orbit hold still while a
+/// selection is being dragged.
+pub(crate) fn apply_camera_mode(
+    mode: Res<CameraMode>,
+    actions: Res<Actions>,
+    selection: Option<Res<Selection>>,
+    mut unreal: Query<&mut UnrealCameraController>,
+    mut orbit: Query<&mut OrbitCameraController>,
+) {
+    let selecting =
+        actions.pressed(Action::Select) || selection.is_some_and(|selection| selection.dragging());
+    for mut controller in unreal.iter_mut() {
+        controller.enabled = *mode == CameraMode::Fly && !selecting;
+    }
+    for mut controller in orbit.iter_mut() {
+        controller.enabled = *mode == CameraMode::Orbit && !selecting;
+    }
+}
+
+/// The move actions or the cursor at the window edge pan, the scroll wheel zooms while panning
+/// is held. The edge
+/// doesn't pan while the cursor is on a UI node, like the hotbar or the minimap.
+pub(crate) fn rts_camera(
+    mode: Res<CameraMode>,
+    actions: Res<Actions>,
+    time: Res<Time>,
+    mut wheel: EventReader<MouseWheel>,
+    windows: Query<&Window>,
+    ui_nodes: Query<&RelativeCursorPosition>,
+    mut cameras: Query<&mut LookTransform, With<Player>>,
+) {
+    let mut scrolled: f32 = wheel.read().map(|ev| ev.y).sum();
+    if *mode != CameraMode::Rts {
+        return;
+    }
+    if !mode.uses_wheel(&actions) {
+        scrolled = 0.0;
+    }
+    let mut pan = Vec2::ZERO;
+    for (action, direction) in [
+        (Action::MoveForward, Vec2::NEG_Y),
+        (Action::MoveBack, Vec2::Y),
+        (Action::MoveLeft, Vec2::NEG_X),
+        (Action::MoveRight, Vec2::X),
+    ] {
+        if actions.pressed(action) {
+            pan += direction;
+        }
+    }
+    let on_ui = ui_nodes.iter().any(RelativeCursorPosition::mouse_over);
+    let edge_cursor = windows
+        .get_single()
+        .ok()
+        .filter(|_| !on_ui)
+        .and_then(|window| {
+            let size = window.size();
+            window.cursor_position().map(|cursor| (cursor, size))
+        });
+    if let Some(cursor) = edge_cursor {
+        let (cursor, size) = cursor;
+        if cursor.x < RTS_EDGE {
+            pan.x -= 1.0;
+        } else if cursor.x > size.x - RTS_EDGE {
+            pan.x += 1.0;
+        }
+        if cursor.y < RTS_EDGE {
+            pan.y -= 1.0;
+        } else if cursor.y > size.y - RTS_EDGE {
+            pan.y += 1.0;
+        }
+    }
+
+    for mut look in cameras.iter_mut() {
+        let mut height = (look.eye.y - look.target.y) * (1.0 - scrolled * RTS_ZOOM_STEP);
+        height = height.clamp(RTS_MIN_HEIGHT, RTS_MAX_HEIGHT);
+        // Pan faster when zoomed out
+        let pan = pan.normalize_or_zero() * RTS_PAN_SPEED * height * time.delta_secs();
+        look.target += Vec3::new(pan.x, 0.0, pan.y);
+        look.eye = rts_eye(look.target, height);
+    }
+}
+
+/// Input of the fly camera, the default input of `UnrealCameraController` driven by actions.
+/// Dragging moves forward and turns, looking rotates, panning or dragging while looking moves
+/// sideways. The move actions fly while one of those is held, the scroll wheel changes the flying
+/// speed.
+pub(crate) fn fly_camera_input(
+    actions: Res<Actions>,
+    mut events: EventWriter<ControlEvent>,
+    mut wheel: EventReader<MouseWheel>,
+    mut motion: EventReader<MouseMotion>,
+    mut controllers: Query<&mut UnrealCameraController>,
+) {
+    let cursor_delta: Vec2 = motion.read().map(|ev| ev.delta).sum();
+    let wheel_delta: f32 = wheel.read().map(|ev| ev.x + ev.y).sum();
+    let Some(mut controller) = controllers.iter_mut().find(|c| c.enabled) else {
+        return;
+    };
+    let drag = actions.pressed(Action::Drag);
+    let look = actions.pressed(Action::Look);
+    let pan = actions.pressed(Action::Pan);
+
+    let mut panning_dir = Vec2::ZERO;
+    let mut forward = 0.0;
+    for (action, direction) in [
+        (Action::MoveUp, Vec2::Y),
+        (Action::MoveDown, Vec2::NEG_Y),
+        (Action::MoveLeft, Vec2::NEG_X),
+        (Action::MoveRight, Vec2::X),
+    ] {
+        if actions.pressed(action) {
+            panning_dir += direction;
+        }
+    }
+    if actions.pressed(Action::MoveForward) {
+        forward += 1.0;
+    }
+    if actions.pressed(Action::MoveBack) {
+        forward -= 1.0;
+    }
+
+    let mut panning = Vec2::ZERO;
+    let mut locomotion = Vec2::ZERO;
+    if drag || look || pan {
+        panning += controller.keyboard_mvmt_sensitivity * panning_dir;
+        locomotion.y += controller.keyboard_mvmt_sensitivity * forward;
+        // The wheel changes the flying speed while flying, otherwise it is the hotbar's
+        controller.keyboard_mvmt_sensitivity = (controller.keyboard_mvmt_sensitivity
+            + controller.keyboard_mvmt_wheel_sensitivity * wheel_delta)
+            .max(0.01);
+    }
+    if pan || (drag && look) {
+        panning += controller.mouse_translate_sensitivity * cursor_delta;
+    }
+    if drag && !pan && !look {
+        locomotion.x = controller.rotate_sensitivity.x * cursor_delta.x;
+        locomotion.y -= controller.mouse_translate_sensitivity.y * cursor_delta.y;
+    }
+    if look && !drag && !pan {
+        events.send(ControlEvent::Rotate(
+            controller.rotate_sensitivity * cursor_delta,
+        ));
+    }
+    if panning.length_squared() > 0.0 {
+        events.send(ControlEvent::TranslateEye(panning));
+    }
+    if locomotion.length_squared() > 0.0 {
+        events.send(ControlEvent::Locomotion(locomotion));
+    }
+}
+
+/// Input of the orbit camera, the default input of `OrbitCameraController` driven by actions.
+/// Orbiting and panning follow the mouse, zooming follows it up and down, or the scroll wheel
+/// while one of them is held.
+pub(crate) fn orbit_camera_input(
+    actions: Res<Actions>,
+    mut events: EventWriter<orbit::ControlEvent>,
+    mut wheel: EventReader<MouseWheel>,
+    mut motion: EventReader<MouseMotion>,
+    controllers: Query<&OrbitCameraController>,
+) {
+    let cursor_delta: Vec2 = motion.read().map(|ev| ev.delta).sum();
+    let wheel: Vec<MouseWheel> = wheel.read().cloned().collect();
+    let Some(controller) = controllers.iter().find(|c| c.enabled) else {
+        return;
+    };
+
+    let mut zoom = 1.0;
+    if actions.pressed(Action::Zoom) {
+        zoom *= 1.0 + cursor_delta.y * ORBIT_DRAG_ZOOM;
+    } else if actions.pressed(Action::Orbit) {
+        events.send(orbit::ControlEvent::Orbit(
+            controller.mouse_rotate_sensitivity * cursor_delta,
+        ));
+    }
+    if actions.pressed(Action::Pan) {
+        events.send(orbit::ControlEvent::TranslateTarget(
+            controller.mouse_translate_sensitivity * cursor_delta,
+        ));
+    }
+    // Without a button held the wheel is the hotbar's
+    let wheel = wheel
+        .into_iter()
+        .filter(|_| CameraMode::Orbit.uses_wheel(&actions));
+    for ev in wheel {
+        let lines = match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / controller.pixels_per_line,
+        };
+        zoom *= 1.0 - lines * controller.mouse_wheel_zoom_sensitivity;
+    }
+    if zoom != 1.0 {
+        events.send(orbit::ControlEvent::Zoom(zoom));
+    }
+}
+
+/// Where the demo camera starts and where its bookmarks are kept.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct DemoCameraSettings {
+    pub position: Vec3,
+    pub look_at: Vec3,
+    /// Bookmarks file, a broken file is left alone and bookmarks can't be saved until it is fixed.
+    pub bookmarks: PathBuf,
+}
+
+impl Default for DemoCameraSettings {
+    fn default() -> Self {
+        Self {
+            position: Vec3::splat(10.0),
+            look_at: Vec3::ZERO,
+            bookmarks: PathBuf::from(bookmarks::DEFAULT_CAMERA_FILE),
+        }
+    }
+}
+
+/// The systems moving the demo camera.
+#[derive(SystemSet, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct DemoCameraSet;
+
+/// The camera of the demo, with the fly, orbit, RTS and first person modes and camera bookmarks.
+/// Chunks of both voxel worlds spawn around it, so it needs the terrain and water plugins.
+#[derive(Default)]
+pub struct DemoCameraPlugin {
+    pub settings: DemoCameraSettings,
+}
+
+impl Plugin for DemoCameraPlugin {
+    fn build(&self, app: &mut App) {
+        let bookmarks = CameraBookmarks::load(&self.settings.bookmarks).unwrap_or_else(|err| {
+            warn!("{}", err);
+            CameraBookmarks::default()
+        });
+        input::add_actions(app);
+        app.add_plugins((
+            LookTransformPlugin,
+            // Both cameras are driven by actions instead of their default input
+            UnrealCameraPlugin::new(true),
+            OrbitCameraPlugin::new(true),
+        ))
+        .insert_resource(self.settings.clone())
+        .insert_resource(bookmarks)
+        .init_resource::<CameraMode>()
+        .init_resource::<VoxelTrace>()
+        .add_systems(Startup, spawn_camera)
+        .add_systems(
+            Update,
+            (
+                switch_camera_mode,
+                apply_camera_mode,
+                rts_camera,
+                fly_camera_input,
+                orbit_camera_input,
+                bookmarks::camera_bookmark_keys,
+                bookmarks::play_camera_path,
+                player::first_person_controller,
+            )
+                .chain()
+                .in_set(DemoCameraSet),
+        );
+    }
+}
+
+fn spawn_camera(mut commands: Commands, settings: Res<DemoCameraSettings>) {
+    commands
+        .spawn((
+            Camera3d::default(),
+            Transform::from_translation(settings.position).looking_at(settings.look_at, Vec3::Y),
+            // This tells bevy_voxel_world to use this cameras transform to calculate spawning area
+            VoxelWorldCamera::<MyMainWorld>::default(),
+            VoxelWorldCamera::<WaterWorld>::default(),
+            Player::default(),
+        ))
+        .insert(UnrealCameraBundle::new(
+            UnrealCameraController::default(),
+            settings.position,
+            settings.look_at,
+            Vec3::Y,
+        ))
+        // Shares the look transform with the fly camera, the camera mode enables one of them
+        .insert(OrbitCameraController {
+            enabled: false,
+            ..default()
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_cycle_back_to_fly() {
+        let mut sut = CameraMode::default();
+        for _ in 0..4 {
+            sut = sut.next();
+        }
+        assert_eq!(sut, CameraMode::Fly);
+    }
+
+    #[test]
+    fn wheel_is_the_hotbars_unless_a_camera_button_is_held() {
+        let idle = Actions::default();
+        for mode in [
+            CameraMode::Fly,
+            CameraMode::Orbit,
+            CameraMode::Rts,
+            CameraMode::FirstPerson,
+        ] {
+            assert!(!mode.uses_wheel(&idle), "{:?} takes the wheel", mode);
+        }
+        let looking = Actions::pressing([Action::Look]);
+        assert!(CameraMode::Fly.uses_wheel(&looking));
+        assert!(!CameraMode::FirstPerson.uses_wheel(&looking));
+        assert!(CameraMode::Orbit.uses_wheel(&Actions::pressing([Action::Orbit])));
+    }
+}
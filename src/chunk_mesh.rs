@@ -0,0 +1,190 @@
+use crate::blocks::MAX_LIGHT;
+use crate::lighting::BlockLight;
+use crate::map::Map;
+use crate::textures::{retint_tile, tint_level};
+use crate::MyMainWorld;
+use bevy::prelude::*;
+use bevy::render::mesh::{MeshVertexAttribute, VertexAttributeValues};
+use bevy::render::render_resource::VertexFormat;
+use bevy::utils::HashMap;
+use bevy_voxel_world::prelude::*;
+
+/// The `texture_index_mapper` output, the same attribute as the crate private one in
+/// `bevy_voxel_world`.
+const ATTRIBUTE_TEX_INDEX: MeshVertexAttribute =
+    MeshVertexAttribute::new("TextureIndex", 989640910, VertexFormat::Uint32x3);
+
+/// Marks a chunk whose mesh has been baked. Holds on to the mesh from `bevy_voxel_world`, so it
+/// can be baked again when the light changes.
+#[derive(Component)]
+pub(crate) struct BakedMesh {
+    source: Handle<Mesh>,
+    baked: AssetId<Mesh>,
+}
+
+/// The tint and block light of every quad of a chunk mesh, what is baked into it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Bake {
+    tints: Vec<Option<u32>>,
+    light: Vec<u8>,
+}
+
+impl Bake {
+    fn of(mesh: &Mesh, origin: Vec3, map: &Map, light: &BlockLight) -> Self {
+        Self {
+            tints: quad_voxels(mesh, origin)
+                .into_iter()
+                .map(|voxel| map.get(voxel).map(|node| tint_level(node.moisture)))
+                .collect(),
+            light: quad_front_voxels(mesh, origin)
+                .into_iter()
+                .map(|voxel| light.get(voxel))
+                .collect(),
+        }
+    }
+
+    fn apply(&self, mesh: &mut Mesh) {
+        tint_mesh(mesh, &self.tints);
+        light_mesh(mesh, &self.light);
+    }
+}
+
+/// Baked copies of source meshes, so chunks with the same mesh and bake share one copy like
+/// `bevy_voxel_world` shares the source meshes.
+#[derive(Resource, Default)]
+pub(crate) struct BakedMeshes(HashMap<(AssetId<Mesh>, Bake), AssetId<Mesh>>);
+
+/// Terrain chunk meshes come out of `bevy_voxel_world` knowing only the voxel materials.
+/// Each new chunk mesh is copied and the data that depends on where the voxels are in the world
+/// is baked into the copy.
+#[allow(clippy::type_complexity)]
+pub(crate) fn bake_chunk_meshes(
+    mut commands: Commands,
+    world: Res<MyMainWorld>,
+    mut light: ResMut<BlockLight>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut baked_meshes: ResMut<BakedMeshes>,
+    chunks: Query<(
+        Entity,
+        &Chunk<MyMainWorld>,
+        &Mesh3d,
+        &Transform,
+        Option<&BakedMesh>,
+    )>,
+) {
+    let relit = light.take_dirty_chunks();
+    let mut added = false;
+    for (entity, chunk, mesh3d, transform, baked) in chunks.iter() {
+        let source = match baked {
+            Some(baked) if baked.baked == mesh3d.id() => {
+                if !relit.contains(&chunk.position) {
+                    continue;
+                }
+                baked.source.clone()
+            }
+            _ => mesh3d.0.clone(),
+        };
+        // The mesh from bevy_voxel_world can be shared with other chunks through its mesh cache,
+        // so it is never baked in place
+        let Some(mesh) = meshes.get(&source) else {
+            continue;
+        };
+        let key = (
+            source.id(),
+            Bake::of(mesh, transform.translation, &world.static_map, &light),
+        );
+        let cached = baked_meshes
+            .0
+            .get(&key)
+            .and_then(|id| meshes.get_strong_handle(*id));
+        let handle = match cached {
+            Some(handle) => handle,
+            None => {
+                let Some(mut mesh) = meshes.get(&source).cloned() else {
+                    continue;
+                };
+                key.1.apply(&mut mesh);
+                let handle = meshes.add(mesh);
+                baked_meshes.0.insert(key, handle.id());
+                added = true;
+                handle
+            }
+        };
+        commands.entity(entity).try_insert((
+            BakedMesh {
+                source,
+                baked: handle.id(),
+            },
+            Mesh3d(handle),
+        ));
+    }
+    if added {
+        // Forget the copies no chunk uses any more
+        baked_meshes.0.retain(|_, id| meshes.contains(*id));
+    }
+}
+
+/// World position of the voxel each quad of the mesh belongs to.
+fn quad_voxels(mesh: &Mesh, origin: Vec3) -> Vec<IVec3> {
+    quad_voxels_on_side(mesh, origin, -0.5)
+}
+
+/// World position of the voxel in front of each quad of the mesh, the one its face looks into.
+fn quad_front_voxels(mesh: &Mesh, origin: Vec3) -> Vec<IVec3> {
+    quad_voxels_on_side(mesh, origin, 0.5)
+}
+
+fn quad_voxels_on_side(mesh: &Mesh, origin: Vec3, side: f32) -> Vec<IVec3> {
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32x3(normals)),
+    ) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+    )
+    else {
+        return Vec::new();
+    };
+
+    // Chunk meshes are built from quads of 4 vertices, all with the normal of the face
+    positions
+        .chunks(4)
+        .zip(normals.chunks(4))
+        .map(|(quad, normals)| {
+            let center = quad.iter().map(|p| Vec3::from(*p)).sum::<Vec3>() / 4.0;
+            (origin + center + Vec3::from(normals[0]) * side)
+                .floor()
+                .as_ivec3()
+        })
+        .collect()
+}
+
+/// Set the tint of tinted tiles from the moisture of the map column they are in.
+fn tint_mesh(mesh: &mut Mesh, tints: &[Option<u32>]) {
+    let Some(VertexAttributeValues::Uint32x3(tex_idx)) = mesh.attribute_mut(ATTRIBUTE_TEX_INDEX)
+    else {
+        return;
+    };
+    for (quad, tint) in tex_idx.chunks_mut(4).zip(tints) {
+        let Some(tint) = *tint else {
+            continue;
+        };
+        for tile in quad.iter_mut().flatten() {
+            *tile = retint_tile(*tile, tint);
+        }
+    }
+}
+
+/// Put the block light reaching each face in the vertex color alpha, next to the ambient
+/// occlusion in the color channels.
+fn light_mesh(mesh: &mut Mesh, levels: &[u8]) {
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    for (quad, level) in colors.chunks_mut(4).zip(levels) {
+        for color in quad {
+            color[3] = *level as f32 / MAX_LIGHT as f32;
+        }
+    }
+}
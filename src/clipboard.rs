@@ -0,0 +1,380 @@
+use crate::console::files_in;
+use crate::cursor::CursorCube;
+use crate::editing::WorldEditor;
+use crate::input::{Action, Actions};
+use crate::selection::{Region, Selection, MAX_FILL};
+use crate::shapes::ghost;
+use crate::textures::BlockTexture;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const PREFAB_DIR: &str = "assets/prefabs";
+pub(crate) const PREFAB_EXTENSION: &str = "prefab";
+
+/// A piece of terrain that can be pasted elsewhere, the blocks are relative to its lowest corner.
+///
+/// Prefab files live in `assets/prefabs/<name>.prefab`, with a line per block:
+///
+/// ```text
+/// # x y z block
+/// 0 0 0 StoneBrick
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Prefab {
+    pub(crate) blocks: Vec<(IVec3, BlockTexture)>,
+}
+
+impl Prefab {
+    /// The solid blocks in the region.
+    pub(crate) fn copy(
+        region: Region,
+        get_voxel: impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+    ) -> Self {
+        let blocks = region
+            .voxels()
+            .filter_map(|pos| match get_voxel(pos) {
+                WorldVoxel::Solid(block) => Some((pos - region.min, block)),
+                _ => None,
+            })
+            .collect();
+        Self { blocks }
+    }
+
+    fn map_positions(&self, f: impl Fn(IVec3) -> IVec3) -> Self {
+        let blocks: Vec<(IVec3, BlockTexture)> = self
+            .blocks
+            .iter()
+            .map(|(pos, block)| (f(*pos), *block))
+            .collect();
+        // Move back to start at the origin
+        let min = blocks
+            .iter()
+            .map(|(pos, _)| *pos)
+            .reduce(IVec3::min)
+            .unwrap_or_default();
+        Self {
+            blocks: blocks
+                .into_iter()
+                .map(|(pos, block)| (pos - min, block))
+                .collect(),
+        }
+    }
+
+    /// Turned a quarter clockwise around the Y axis, seen from above.
+    pub(crate) fn rotated(&self) -> Self {
+        self.map_positions(|pos| IVec3::new(-pos.z, pos.y, pos.x))
+    }
+
+    pub(crate) fn mirrored_x(&self) -> Self {
+        self.map_positions(|pos| IVec3::new(-pos.x, pos.y, pos.z))
+    }
+
+    pub(crate) fn mirrored_z(&self) -> Self {
+        self.map_positions(|pos| IVec3::new(pos.x, pos.y, -pos.z))
+    }
+
+    pub(crate) fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut blocks = Vec::new();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: String| format!("{}:{}: {}", name, line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [x, y, z, block] = words.as_slice() else {
+                return Err(error(format!("can't parse '{}'", line)));
+            };
+            let mut pos = IVec3::ZERO;
+            for (axis, word) in [x, y, z].into_iter().enumerate() {
+                pos[axis] = word
+                    .parse()
+                    .map_err(|_| error(format!("invalid coordinate '{}'", word)))?;
+            }
+            blocks.push((pos, block.parse::<BlockTexture>().map_err(error)?));
+        }
+        // Pasting writes every block in one frame, like a fill
+        if blocks.len() as i64 > MAX_FILL {
+            return Err(format!(
+                "{}: {} blocks is too many, a prefab holds at most {}",
+                name,
+                blocks.len(),
+                MAX_FILL
+            ));
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Names of all prefab files found in the prefab directory, sorted.
+    pub(crate) fn available() -> Vec<String> {
+        files_in(PREFAB_DIR, PREFAB_EXTENSION)
+    }
+
+    /// Path of the prefab file called `name` in the prefab directory.
+    pub(crate) fn path(name: &str) -> PathBuf {
+        Path::new(PREFAB_DIR).join(format!("{}.{}", name, PREFAB_EXTENSION))
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        Self::parse(&path.display().to_string(), &source)
+    }
+
+    pub(crate) fn save(&self, name: &str) -> Result<(), String> {
+        let path = Self::path(name);
+        fs::create_dir_all(PREFAB_DIR)
+            .and_then(|_| fs::write(&path, self.to_string()))
+            .map_err(|err| format!("can't write {}: {}", path.display(), err))
+    }
+}
+
+impl fmt::Display for Prefab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# x y z block")?;
+        for (pos, block) in &self.blocks {
+            writeln!(f, "{} {} {} {:?}", pos.x, pos.y, pos.z, block)?;
+        }
+        Ok(())
+    }
+}
+
+/// The prefab after `current` in `available`, wrapping around to the first.
+fn next_prefab<'a>(available: &'a [String], current: Option<&str>) -> Option<&'a String> {
+    current
+        .and_then(|current| available.iter().find(|name| name.as_str() > current))
+        .or_else(|| available.first())
+}
+
+/// A name for a new prefab that no file has yet. Named by time so the names sort in the order
+/// they were saved.
+fn unused_prefab_name() -> String {
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    while Prefab::path(&format!("prefab_{}", millis)).exists() {
+        millis += 1;
+    }
+    format!("prefab_{}", millis)
+}
+
+/// The copied prefab. While pasting, a ghost of it follows the cursor until it is placed.
+#[derive(Resource, Default)]
+pub(crate) struct Clipboard {
+    pub(crate) prefab: Option<Prefab>,
+    pub(crate) pasting: bool,
+    /// Name of the prefab the load key loaded last, the next press loads the one after it.
+    loaded: Option<String>,
+}
+
+/// Copy or cut the selection, toggle pasting, save the clipboard as a prefab or step through
+/// the saved prefabs. While pasting the clipboard can be rotated and mirrored along X and Z.
+/// The `prefab` console command loads a prefab by name.
+pub(crate) fn clipboard_keys(
+    actions: Res<Actions>,
+    selection: Res<Selection>,
+    mut clipboard: ResMut<Clipboard>,
+    mut editor: WorldEditor,
+) {
+    let cut = actions.just_pressed(Action::Cut);
+    if cut || actions.just_pressed(Action::Copy) {
+        let Some(region) = selection.region else {
+            return;
+        };
+        if region.volume() > MAX_FILL {
+            warn!(
+                "The selection is {} voxels, copy at most {}",
+                region.volume(),
+                MAX_FILL
+            );
+            return;
+        }
+        clipboard.prefab = Some(Prefab::copy(region, |pos| editor.get_voxel(pos)));
+        if cut {
+            for pos in region.voxels() {
+                editor.set_voxel(pos, WorldVoxel::Air);
+            }
+        }
+    }
+    if actions.just_pressed(Action::Paste) {
+        clipboard.pasting = !clipboard.pasting && clipboard.prefab.is_some();
+    }
+    if actions.just_pressed(Action::SavePrefab) {
+        if let Some(prefab) = &clipboard.prefab {
+            let name = unused_prefab_name();
+            match prefab.save(&name) {
+                Ok(()) => info!("Saved clipboard as prefab {}", name),
+                Err(err) => error!("{}", err),
+            }
+        }
+    }
+    if actions.just_pressed(Action::LoadPrefab) {
+        let available = Prefab::available();
+        if let Some(name) = next_prefab(&available, clipboard.loaded.as_deref()) {
+            match Prefab::load(&Prefab::path(name)) {
+                Ok(prefab) => {
+                    info!("Loaded prefab {}", name);
+                    clipboard.prefab = Some(prefab);
+                }
+                Err(err) => error!("{}", err),
+            }
+            // A broken file is skipped on the next press
+            clipboard.loaded = Some(name.clone());
+        }
+    }
+
+    if !clipboard.pasting {
+        return;
+    }
+    let Some(prefab) = &clipboard.prefab else {
+        return;
+    };
+    let transformed = if actions.just_pressed(Action::RotatePaste) {
+        prefab.rotated()
+    } else if actions.just_pressed(Action::MirrorX) {
+        prefab.mirrored_x()
+    } else if actions.just_pressed(Action::MirrorZ) {
+        prefab.mirrored_z()
+    } else {
+        return;
+    };
+    clipboard.prefab = Some(transformed);
+}
+
+/// Placing puts the clipboard down with its lowest corner at the cursor, instead of the cursor
+/// or a shape placing blocks.
+pub(crate) fn paste(
+    mut actions: ResMut<Actions>,
+    clipboard: Res<Clipboard>,
+    mut editor: WorldEditor,
+    cursor_cube: Query<&CursorCube>,
+) {
+    if !clipboard.pasting || !actions.just_pressed(Action::Place) {
+        return;
+    }
+    actions.consume(Action::Place);
+    let Some(prefab) = &clipboard.prefab else {
+        return;
+    };
+    let origin = cursor_cube.single().voxel_pos;
+    for (pos, block) in &prefab.blocks {
+        editor.set_voxel(origin + *pos, WorldVoxel::Solid(*block));
+    }
+}
+
+/// Ghost of the clipboard at the cursor while pasting. The ghost is only built again when the
+/// clipboard changes.
+pub(crate) fn draw_paste_preview(
+    clipboard: Res<Clipboard>,
+    cursor_cube: Query<&CursorCube>,
+    mut preview: Local<Vec<IVec3>>,
+    mut gizmos: Gizmos,
+) {
+    if clipboard.is_changed() {
+        *preview = clipboard.prefab.as_ref().map_or_else(Vec::new, |prefab| {
+            ghost(prefab.blocks.iter().map(|(pos, _)| *pos).collect())
+        });
+    }
+    if !clipboard.pasting {
+        return;
+    }
+    let origin = cursor_cube.single().voxel_pos;
+    for pos in preview.iter() {
+        gizmos.cuboid(
+            Transform::from_translation((origin + *pos).as_vec3() + Vec3::splat(0.5)),
+            Color::srgba(0.6, 1.0, 0.6, 0.5),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l_shape() -> Prefab {
+        Prefab {
+            blocks: vec![
+                (IVec3::ZERO, BlockTexture::StoneBrick),
+                (IVec3::X, BlockTexture::StoneBrick),
+                (IVec3::new(2, 0, 0), BlockTexture::StoneBrick),
+                (IVec3::new(0, 1, 1), BlockTexture::RockBrick),
+            ],
+        }
+    }
+
+    #[test]
+    fn copy_keeps_solids_relative_to_corner() {
+        let region = Region::from_corners(IVec3::new(5, 5, 5), IVec3::new(6, 6, 6));
+        let sut = Prefab::copy(region, |pos| {
+            if pos == IVec3::new(6, 5, 5) {
+                WorldVoxel::Solid(BlockTexture::SandBrick)
+            } else {
+                WorldVoxel::Air
+            }
+        });
+        assert_eq!(sut.blocks, vec![(IVec3::X, BlockTexture::SandBrick)]);
+    }
+
+    #[test]
+    fn four_rotations_are_identity() {
+        let sut = l_shape();
+        let rotated = sut.rotated();
+        assert!(rotated
+            .blocks
+            .contains(&(IVec3::new(1, 0, 2), BlockTexture::StoneBrick)));
+        assert!(rotated
+            .blocks
+            .contains(&(IVec3::new(0, 1, 0), BlockTexture::RockBrick)));
+        assert_eq!(rotated.rotated().rotated().rotated(), sut);
+    }
+
+    #[test]
+    fn mirror_stays_at_origin() {
+        let sut = l_shape().mirrored_x();
+        assert!(sut
+            .blocks
+            .contains(&(IVec3::new(2, 1, 1), BlockTexture::RockBrick)));
+        assert_eq!(sut.mirrored_x(), l_shape());
+        assert_eq!(l_shape().mirrored_z().mirrored_z(), l_shape());
+    }
+
+    #[test]
+    fn prefab_round_trips_through_text() {
+        let sut = l_shape();
+        assert_eq!(Prefab::parse("test", &sut.to_string()), Ok(sut));
+        assert_eq!(
+            Prefab::parse("test", "0 0 Stone"),
+            Err("test:1: can't parse '0 0 Stone'".to_string())
+        );
+    }
+
+    #[test]
+    fn prefab_larger_than_fill_is_refused() {
+        let source = "0 0 0 StoneBrick\n".repeat(MAX_FILL as usize + 1);
+        assert_eq!(
+            Prefab::parse("test", &source),
+            Err("test: 32769 blocks is too many, a prefab holds at most 32768".to_string())
+        );
+    }
+
+    #[test]
+    fn load_key_steps_through_prefabs() {
+        let available = ["prefab_1", "prefab_2", "tower"].map(String::from);
+        assert_eq!(next_prefab(&available, None), Some(&available[0]));
+        assert_eq!(
+            next_prefab(&available, Some("prefab_1")),
+            Some(&available[1])
+        );
+        assert_eq!(next_prefab(&available, Some("tower")), Some(&available[0]));
+        // A loaded prefab that was removed continues from where it was
+        assert_eq!(
+            next_prefab(&available, Some("prefab_15")),
+            Some(&available[1])
+        );
+        assert_eq!(next_prefab(&[], Some("tower")), None);
+    }
+}
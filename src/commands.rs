@@ -0,0 +1,357 @@
+use crate::blocks::{is_collidable, BlockProperties};
+use crate::camera::CameraMode;
+use crate::clipboard::{Clipboard, Prefab, PREFAB_DIR, PREFAB_EXTENSION};
+use crate::console::{Arg, ArgKind, Args, Command, CommandRegistry, Value};
+use crate::editing::{EditedVoxels, WorldEditor};
+use crate::history::EditHistory;
+use crate::map::Map;
+use crate::map_file::{MapFile, MAPS_DIR, MAP_EXTENSION};
+use crate::player::Player;
+use crate::scripting::{run_script_file, SCRIPTS_DIR, SCRIPT_EXTENSION};
+use crate::selection::{Region, MAX_FILL};
+use crate::terrain::terrain_voxel;
+use crate::textures::BlockTexture;
+use crate::water::{spring_flow, water_voxel};
+use crate::{MyMainWorld, WaterWorld, WorldGen};
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+use smooth_bevy_cameras::LookTransform;
+use std::sync::Arc;
+
+/// Most voxels of water a spring runs into.
+const SPRING_VOLUME: usize = 2000;
+const MAP_FILE: ArgKind = ArgKind::File {
+    dir: MAPS_DIR,
+    extension: MAP_EXTENSION,
+};
+/// Heights searched for the ground a spring is put on.
+const SPRING_LOWEST: i32 = -64;
+const SPRING_HIGHEST: i32 = 128;
+
+/// The registry with the built-in commands.
+pub(crate) fn builtin() -> CommandRegistry {
+    let mut registry = CommandRegistry::default();
+    let corner = |names: [&'static str; 3]| names.map(|name| Arg::new(name, ArgKind::Int));
+    registry.register(Command {
+        name: "tp",
+        args: ["x", "y", "z"]
+            .map(|name| Arg::new(name, ArgKind::Float))
+            .to_vec(),
+        help: "Move the camera to a position, looking the same way.",
+        run: teleport,
+    });
+    registry.register(Command {
+        name: "seed",
+        args: vec![Arg::new("n", ArgKind::Uint).optional()],
+        help: "Show the seed of the terrain noise, or set the one regen uses.",
+        run: seed,
+    });
+    registry.register(Command {
+        name: "regen",
+        args: Vec::new(),
+        help: "Generate the map again from the seed, dropping all edits.",
+        run: regenerate,
+    });
+    registry.register(Command {
+        name: "fill",
+        args: [
+            corner(["x1", "y1", "z1"]).as_slice(),
+            corner(["x2", "y2", "z2"]).as_slice(),
+            &[Arg::new("block", ArgKind::Block)],
+        ]
+        .concat(),
+        help: "Fill the box between two corners with a block, Air clears it.",
+        run: fill,
+    });
+    registry.register(Command {
+        name: "save",
+        args: vec![Arg::new("file", MAP_FILE)],
+        help: "Save the map with its edits, a name without a path goes to assets/maps.",
+        run: save,
+    });
+    registry.register(Command {
+        name: "load",
+        args: vec![Arg::new("file", MAP_FILE)],
+        help: "Load a saved map and its edits.",
+        run: load,
+    });
+    registry.register(Command {
+        name: "water",
+        args: vec![
+            Arg::new("spring", ArgKind::Word("spring")),
+            Arg::new("x", ArgKind::Int),
+            Arg::new("z", ArgKind::Int),
+        ],
+        help: "Start a spring on the ground of a column, the water runs downhill.",
+        run: water_spring,
+    });
+    registry.register(Command {
+        name: "script",
+        args: vec![Arg::new(
+            "file",
+            ArgKind::File {
+                dir: SCRIPTS_DIR,
+                extension: SCRIPT_EXTENSION,
+            },
+        )],
+        help: "Run a script, a name without a path is looked up in assets/scripts.",
+        run: script,
+    });
+    registry.register(Command {
+        name: "prefab",
+        args: vec![Arg::new(
+            "file",
+            ArgKind::File {
+                dir: PREFAB_DIR,
+                extension: PREFAB_EXTENSION,
+            },
+        )],
+        help: "Load a prefab to paste, a name without a path is looked up in assets/prefabs.",
+        run: prefab,
+    });
+    registry
+}
+
+/// Swap the map the worlds are generated from. Chunks are generated again and the voxels
+/// changed by edits are reset to the new terrain, nothing of the old map is left.
+pub(crate) fn replace_map(world: &mut World, map: Map) {
+    let map = Arc::new(map);
+    world.resource_mut::<MyMainWorld>().static_map = map.clone();
+    world.resource_mut::<WaterWorld>().static_map = map.clone();
+
+    let edited = std::mem::take(&mut *world.resource_mut::<EditedVoxels>());
+    let mut state = SystemState::<WorldEditor>::new(world);
+    let mut editor = state.get_mut(world);
+    for pos in edited.terrain.keys() {
+        editor.restore(*pos, terrain_voxel(&map, *pos));
+    }
+    for pos in edited.water.keys() {
+        editor.restore_water(*pos, water_voxel(&map, *pos));
+    }
+    state.apply(world);
+    // Resetting isn't editing, and the old edits can't be undone on the new map
+    *world.resource_mut::<EditedVoxels>() = EditedVoxels::default();
+    world.resource_mut::<EditHistory>().clear();
+
+    let chunks: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<Chunk<MyMainWorld>>, With<Chunk<WaterWorld>>)>>()
+        .iter(world)
+        .collect();
+    for chunk in chunks {
+        world.entity_mut(chunk).insert(NeedsDespawn);
+    }
+}
+
+fn teleport(world: &mut World, args: &Args) -> Result<String, String> {
+    let to = Vec3::new(args.float(0), args.float(1), args.float(2));
+    let mode = *world.resource::<CameraMode>();
+    let mut cameras = world.query::<(&mut LookTransform, &mut Player)>();
+    for (mut look, mut player) in cameras.iter_mut(world) {
+        let delta = to - look.eye;
+        look.eye += delta;
+        look.target += delta;
+        if mode == CameraMode::FirstPerson {
+            *player = Player::looking_from(&look);
+        }
+    }
+    Ok(format!("Moved to {} {} {}", to.x, to.y, to.z))
+}
+
+fn seed(world: &mut World, args: &Args) -> Result<String, String> {
+    let mut gen = world.resource_mut::<WorldGen>();
+    match args.get(0) {
+        Some(Value::Uint(seed)) => {
+            gen.params.seed = *seed;
+            Ok(format!("Seed set to {}, regen to use it", seed))
+        }
+        _ => Ok(format!("Seed {}", gen.params.seed)),
+    }
+}
+
+fn regenerate(world: &mut World, _args: &Args) -> Result<String, String> {
+    let mut gen = world.resource_mut::<WorldGen>();
+    gen.map_seed = Some(gen.params.seed);
+    let map = Map::generate(gen.size, &gen.params);
+    let (extent, seed) = (map.extent(), gen.params.seed);
+    replace_map(world, map);
+    Ok(format!(
+        "Generated a {}x{} map from seed {}",
+        extent.x, extent.y, seed
+    ))
+}
+
+fn fill(world: &mut World, args: &Args) -> Result<String, String> {
+    let region = Region::from_corners(
+        IVec3::new(args.int(0), args.int(1), args.int(2)),
+        IVec3::new(args.int(3), args.int(4), args.int(5)),
+    );
+    let volume = region.volume();
+    if volume > MAX_FILL {
+        return Err(format!(
+            "{} voxels is too many, fill at most {}",
+            volume, MAX_FILL
+        ));
+    }
+    let block = args.block(6);
+    let mut state = SystemState::<WorldEditor>::new(world);
+    let mut editor = state.get_mut(world);
+    for pos in region.voxels() {
+        editor.set_voxel(pos, block);
+    }
+    state.apply(world);
+    Ok(format!("Filled {} voxels", volume))
+}
+
+fn save(world: &mut World, args: &Args) -> Result<String, String> {
+    let path = args.path(0);
+    let edited = world.resource::<EditedVoxels>();
+    let mut voxels: Vec<_> = edited.terrain.iter().map(|(pos, v)| (*pos, *v)).collect();
+    let mut water: Vec<_> = edited.water.iter().map(|(pos, v)| (*pos, *v)).collect();
+    voxels.sort_by_key(|(pos, _)| pos.to_array());
+    water.sort_by_key(|(pos, _)| pos.to_array());
+    let file = MapFile {
+        map: world.resource::<MyMainWorld>().static_map.as_ref().clone(),
+        seed: world.resource::<WorldGen>().map_seed,
+        voxels,
+        water,
+    };
+    file.save(path)?;
+    Ok(format!("Saved {}", path.display()))
+}
+
+fn load(world: &mut World, args: &Args) -> Result<String, String> {
+    let path = args.path(0);
+    let file = MapFile::load(path)?;
+    let mut gen = world.resource_mut::<WorldGen>();
+    gen.map_seed = file.seed;
+    if let Some(seed) = file.seed {
+        gen.params.seed = seed;
+    }
+    replace_map(world, file.map.clone());
+
+    let mut state = SystemState::<WorldEditor>::new(world);
+    restore_edits(&mut state.get_mut(world), &file);
+    state.apply(world);
+    Ok(format!("Loaded {}", path.display()))
+}
+
+/// Put the edits saved with a map back on it.
+fn restore_edits(editor: &mut WorldEditor, file: &MapFile) {
+    for (pos, voxel) in &file.voxels {
+        editor.restore(*pos, *voxel);
+    }
+    for (pos, voxel) in &file.water {
+        editor.restore_water(*pos, *voxel);
+    }
+}
+
+/// The map file the app started with, its edits are put back once the worlds exist.
+#[derive(Resource)]
+pub(crate) struct StartupMap(pub(crate) MapFile);
+
+pub(crate) fn restore_startup_edits(startup: Option<Res<StartupMap>>, mut editor: WorldEditor) {
+    if let Some(startup) = startup {
+        restore_edits(&mut editor, &startup.0);
+    }
+}
+
+fn script(world: &mut World, args: &Args) -> Result<String, String> {
+    run_script_file(world, args.path(0))
+}
+
+fn prefab(world: &mut World, args: &Args) -> Result<String, String> {
+    let path = args.path(0);
+    let prefab = Prefab::load(path)?;
+    let blocks = prefab.blocks.len();
+    world.resource_mut::<Clipboard>().prefab = Some(prefab);
+    Ok(format!("Loaded {} blocks from {}", blocks, path.display()))
+}
+
+fn water_spring(world: &mut World, args: &Args) -> Result<String, String> {
+    let (x, z) = (args.int(1), args.int(2));
+    let map = world.resource::<MyMainWorld>().static_map.clone();
+    if map.get(IVec3::new(x, 0, z)).is_none() {
+        return Err(format!("{} {} is outside the map", x, z));
+    }
+    let mut state = SystemState::<WorldEditor>::new(world);
+    let mut editor = state.get_mut(world);
+
+    // Chunks that aren't generated yet hold the map's voxels
+    let terrain = |pos: IVec3| match editor.get_voxel(pos) {
+        WorldVoxel::Unset => terrain_voxel(&map, pos),
+        voxel => voxel,
+    };
+    let open = |pos: IVec3| {
+        let water = match editor.get_water(pos) {
+            WorldVoxel::Unset => water_voxel(&map, pos),
+            voxel => voxel,
+        };
+        map.get(pos).is_some()
+            && BlockProperties::of(terrain(pos)).replaceable()
+            && !matches!(water, WorldVoxel::Solid(_))
+    };
+    let ground = (SPRING_LOWEST..=SPRING_HIGHEST)
+        .rev()
+        .find(|y| is_collidable(terrain(IVec3::new(x, *y, z))))
+        .ok_or_else(|| format!("no ground at {} {}", x, z))?;
+    let source = IVec3::new(x, ground + 1, z);
+    let flow = spring_flow(source, open, SPRING_VOLUME);
+
+    for pos in &flow {
+        editor.set_voxel(*pos, WorldVoxel::Solid(BlockTexture::WaterBrick));
+    }
+    state.apply(world);
+    Ok(format!(
+        "Spring at {} {} {} ran into {} voxels",
+        source.x,
+        source.y,
+        source.z,
+        flow.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<CameraMode>();
+        world.spawn((
+            LookTransform::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 2.0, 0.0), Vec3::Y),
+            Player::default(),
+        ));
+        world
+    }
+
+    #[test]
+    fn tp_keeps_direction() {
+        let mut world = world();
+        let sut = builtin();
+        assert_eq!(
+            sut.run(&mut world, "tp 10 20 -5.5"),
+            Ok("Moved to 10 20 -5.5".into())
+        );
+        let look = world.query::<&LookTransform>().single(&world);
+        assert_eq!(look.eye, Vec3::new(10.0, 20.0, -5.5));
+        assert_eq!(look.target, Vec3::new(10.0, 20.0, -8.5));
+    }
+
+    #[test]
+    fn usage_of_builtin_commands() {
+        let sut = builtin();
+        let usage = |name| sut.get(name).unwrap().usage();
+        assert_eq!(usage("fill"), "fill <x1> <y1> <z1> <x2> <y2> <z2> <block>");
+        assert_eq!(usage("water"), "water spring <x> <z>");
+        assert_eq!(usage("seed"), "seed [n]");
+        assert_eq!(
+            sut.run(&mut world(), "fill 0 0 0 100 100 100 Lava"),
+            Err("1030301 voxels is too many, fill at most 32768".into())
+        );
+        assert_eq!(
+            sut.run(&mut world(), "fill 0 0 0 2047 2047 2047 Lava"),
+            Err("8589934592 voxels is too many, fill at most 32768".into())
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use crate::textures::BlockTexture;
+use bevy::math::IVec3;
+use bevy_voxel_world::prelude::WorldVoxel;
+
+/// Blocks in the same group join each other, picking a variant from their neighbours.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub(crate) enum ConnectionGroup {
+    StoneFence,
+    WoodFence,
+    Glass,
+    Track,
+}
+
+const NORTH: u8 = 1;
+const EAST: u8 = 2;
+const SOUTH: u8 = 4;
+const WEST: u8 = 8;
+const UP: u8 = 16;
+const DOWN: u8 = 32;
+
+const HORIZONTAL: [(IVec3, u8); 4] = [
+    (IVec3::NEG_Z, NORTH),
+    (IVec3::X, EAST),
+    (IVec3::Z, SOUTH),
+    (IVec3::NEG_X, WEST),
+];
+
+const VERTICAL: [(IVec3, u8); 2] = [(IVec3::Y, UP), (IVec3::NEG_Y, DOWN)];
+
+impl ConnectionGroup {
+    /// Glass panes also merge vertically, the others only join along the ground.
+    fn directions(&self) -> impl Iterator<Item = (IVec3, u8)> {
+        let vertical: &[(IVec3, u8)] = match self {
+            ConnectionGroup::Glass => &VERTICAL,
+            _ => &[],
+        };
+        HORIZONTAL.into_iter().chain(vertical.iter().copied())
+    }
+
+    /// The block variant to use given the set of connected neighbour directions.
+    fn variant(&self, neighbours: u8) -> BlockTexture {
+        match self {
+            ConnectionGroup::StoneFence if neighbours == 0 => BlockTexture::FenceStonePost,
+            ConnectionGroup::StoneFence => BlockTexture::FenceStone,
+            ConnectionGroup::WoodFence if neighbours == 0 => BlockTexture::FenceWoodPost,
+            ConnectionGroup::WoodFence => BlockTexture::FenceWood,
+            ConnectionGroup::Glass if neighbours == 0 => BlockTexture::GlassFrame,
+            ConnectionGroup::Glass => BlockTexture::GlassPane,
+            ConnectionGroup::Track => track_variant(neighbours),
+        }
+    }
+}
+
+fn track_variant(neighbours: u8) -> BlockTexture {
+    let has = |direction: u8| neighbours & direction != 0;
+    match (has(NORTH), has(EAST), has(SOUTH), has(WEST)) {
+        // Straight runs win over corners at junctions
+        (true, _, true, _) => BlockTexture::TrackNorthSouth,
+        (_, true, _, true) => BlockTexture::TrackEastWest,
+        (true, true, _, _) => BlockTexture::TrackNorthEast,
+        (_, true, true, _) => BlockTexture::TrackSouthEast,
+        (_, _, true, true) => BlockTexture::TrackSouthWest,
+        (true, _, _, true) => BlockTexture::TrackNorthWest,
+        (false, true, false, false) | (false, false, false, true) => BlockTexture::TrackEastWest,
+        _ => BlockTexture::TrackNorthSouth,
+    }
+}
+
+impl BlockTexture {
+    pub(crate) fn connection_group(&self) -> Option<ConnectionGroup> {
+        match self {
+            BlockTexture::FenceStone | BlockTexture::FenceStonePost => {
+                Some(ConnectionGroup::StoneFence)
+            }
+            BlockTexture::FenceWood | BlockTexture::FenceWoodPost => {
+                Some(ConnectionGroup::WoodFence)
+            }
+            BlockTexture::GlassFrame | BlockTexture::GlassPane => Some(ConnectionGroup::Glass),
+            BlockTexture::TrackNorthSouth
+            | BlockTexture::TrackEastWest
+            | BlockTexture::TrackNorthEast
+            | BlockTexture::TrackSouthEast
+            | BlockTexture::TrackSouthWest
+            | BlockTexture::TrackNorthWest => Some(ConnectionGroup::Track),
+            _ => None,
+        }
+    }
+}
+
+fn group_at(
+    get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+    pos: IVec3,
+) -> Option<ConnectionGroup> {
+    match get_voxel(pos) {
+        WorldVoxel::Solid(block) => block.connection_group(),
+        _ => None,
+    }
+}
+
+/// The variant the block at `pos` should have, `None` if it isn't a connecting block.
+fn resolve(
+    get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+    pos: IVec3,
+) -> Option<BlockTexture> {
+    let group = group_at(get_voxel, pos)?;
+    let neighbours = group
+        .directions()
+        .filter(|(offset, _)| group_at(get_voxel, pos + *offset) == Some(group))
+        .fold(0, |mask, (_, direction)| mask | direction);
+    Some(group.variant(neighbours))
+}
+
+/// Variant changes needed at `pos` and around it after the voxel at `pos` has changed.
+pub(crate) fn connected_updates(
+    get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+    pos: IVec3,
+) -> Vec<(IVec3, WorldVoxel<BlockTexture>)> {
+    HORIZONTAL
+        .iter()
+        .chain(VERTICAL.iter())
+        .map(|(offset, _)| pos + *offset)
+        .chain([pos])
+        .filter_map(|pos| {
+            let variant = WorldVoxel::Solid(resolve(get_voxel, pos)?);
+            (variant != get_voxel(pos)).then_some((pos, variant))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+
+    fn world(blocks: &[(IVec3, BlockTexture)]) -> impl Fn(IVec3) -> WorldVoxel<BlockTexture> {
+        let blocks: HashMap<IVec3, BlockTexture> = blocks.iter().copied().collect();
+        move |pos| {
+            blocks
+                .get(&pos)
+                .map(|block| WorldVoxel::Solid(*block))
+                .unwrap_or(WorldVoxel::Air)
+        }
+    }
+
+    #[test]
+    fn single_fence_is_post() {
+        let sut = world(&[(IVec3::ZERO, BlockTexture::FenceWood)]);
+        assert_eq!(
+            connected_updates(&sut, IVec3::ZERO),
+            vec![(IVec3::ZERO, WorldVoxel::Solid(BlockTexture::FenceWoodPost))]
+        );
+    }
+
+    #[test]
+    fn fences_of_different_material_dont_join() {
+        let sut = world(&[
+            (IVec3::ZERO, BlockTexture::FenceWoodPost),
+            (IVec3::X, BlockTexture::FenceStonePost),
+        ]);
+        assert_eq!(connected_updates(&sut, IVec3::X), vec![]);
+    }
+
+    #[test]
+    fn glass_merges_vertically() {
+        let sut = world(&[
+            (IVec3::ZERO, BlockTexture::GlassFrame),
+            (IVec3::Y, BlockTexture::GlassFrame),
+        ]);
+        assert_eq!(
+            connected_updates(&sut, IVec3::Y),
+            vec![
+                (IVec3::ZERO, WorldVoxel::Solid(BlockTexture::GlassPane)),
+                (IVec3::Y, WorldVoxel::Solid(BlockTexture::GlassPane)),
+            ]
+        );
+    }
+
+    #[test]
+    fn track_turns_corner() {
+        let sut = world(&[
+            (IVec3::NEG_Z, BlockTexture::TrackNorthSouth),
+            (IVec3::ZERO, BlockTexture::TrackNorthSouth),
+            (IVec3::X, BlockTexture::TrackEastWest),
+        ]);
+        assert_eq!(
+            connected_updates(&sut, IVec3::X),
+            vec![(IVec3::ZERO, WorldVoxel::Solid(BlockTexture::TrackNorthEast))]
+        );
+    }
+
+    #[test]
+    fn track_end_follows_neighbour() {
+        let sut = world(&[
+            (IVec3::ZERO, BlockTexture::TrackNorthSouth),
+            (IVec3::NEG_X, BlockTexture::TrackNorthSouth),
+        ]);
+        assert_eq!(
+            connected_updates(&sut, IVec3::NEG_X),
+            vec![
+                (IVec3::ZERO, WorldVoxel::Solid(BlockTexture::TrackEastWest)),
+                (IVec3::NEG_X, WorldVoxel::Solid(BlockTexture::TrackEastWest)),
+            ]
+        );
+    }
+}
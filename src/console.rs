@@ -0,0 +1,635 @@
+use crate::input::{Action, Actions, Button, InputMap};
+use crate::textures::BlockTexture;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::WorldVoxel;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lines of output kept by the console.
+const MAX_LOG: usize = 200;
+/// Lines of output shown above the input line.
+const VISIBLE_LINES: usize = 16;
+
+/// What an argument of a command has to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArgKind {
+    Int,
+    Uint,
+    Float,
+    /// A block name, or Air.
+    Block,
+    /// A path, or the name of a file with the extension in the directory, completed from the
+    /// files there.
+    File {
+        dir: &'static str,
+        extension: &'static str,
+    },
+    /// A command name.
+    Command,
+    /// Exactly this word, for commands with subcommands.
+    Word(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Arg {
+    pub(crate) name: &'static str,
+    pub(crate) kind: ArgKind,
+    pub(crate) optional: bool,
+}
+
+impl Arg {
+    pub(crate) const fn new(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub(crate) const fn optional(self) -> Self {
+        Self {
+            optional: true,
+            ..self
+        }
+    }
+
+    fn parse(&self, word: &str) -> Result<Value, String> {
+        let invalid = |what: &str| format!("invalid {} '{}' for <{}>", what, word, self.name);
+        Ok(match self.kind {
+            ArgKind::Int => Value::Int(word.parse().map_err(|_| invalid("number"))?),
+            ArgKind::Uint => Value::Uint(word.parse().map_err(|_| invalid("number"))?),
+            ArgKind::Float => Value::Float(word.parse().map_err(|_| invalid("number"))?),
+            ArgKind::Block => Value::Block(match word {
+                "Air" => WorldVoxel::Air,
+                block => WorldVoxel::Solid(block.parse()?),
+            }),
+            ArgKind::File { dir, extension } => Value::Path(named_file(word, dir, extension)),
+            ArgKind::Command => Value::Text(word.to_string()),
+            ArgKind::Word(expected) if word == expected => Value::Text(word.to_string()),
+            ArgKind::Word(expected) => {
+                return Err(format!("expected '{}', found '{}'", expected, word))
+            }
+        })
+    }
+
+    /// Every value the argument can complete to, none for numbers.
+    fn candidates(&self, registry: &CommandRegistry) -> Vec<String> {
+        match self.kind {
+            ArgKind::Int | ArgKind::Uint | ArgKind::Float => Vec::new(),
+            ArgKind::Block => std::iter::once("Air".to_string())
+                .chain(BlockTexture::ALL.iter().map(|block| format!("{:?}", block)))
+                .collect(),
+            ArgKind::File { dir, extension } => files_in(dir, extension),
+            ArgKind::Command => registry.names().map(str::to_string).collect(),
+            ArgKind::Word(word) => vec![word.to_string()],
+        }
+    }
+}
+
+/// The file of a name in `dir`, or the path itself when it has a directory or extension.
+fn named_file(name: &str, dir: &str, extension: &str) -> PathBuf {
+    let path = Path::new(name);
+    if path.extension().is_some() || path.components().count() > 1 {
+        path.to_path_buf()
+    } else {
+        Path::new(dir).join(name).with_extension(extension)
+    }
+}
+
+/// Names of the files with the extension in `dir`, sorted.
+pub(crate) fn files_in(dir: &str, extension: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Int(i32),
+    Uint(u32),
+    Float(f32),
+    Block(WorldVoxel<BlockTexture>),
+    Text(String),
+    Path(PathBuf),
+}
+
+/// Parsed arguments of a command, in the order it declares them. The typed getters are for
+/// arguments that aren't optional, the command declared them so they are there.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Args(Vec<Value>);
+
+impl Args {
+    pub(crate) fn get(&self, index: usize) -> Option<&Value> {
+        self.0.get(index)
+    }
+
+    pub(crate) fn int(&self, index: usize) -> i32 {
+        match self.0[index] {
+            Value::Int(value) => value,
+            ref other => panic!("argument {} is {:?}, not an int", index, other),
+        }
+    }
+
+    pub(crate) fn float(&self, index: usize) -> f32 {
+        match self.0[index] {
+            Value::Float(value) => value,
+            ref other => panic!("argument {} is {:?}, not a float", index, other),
+        }
+    }
+
+    pub(crate) fn block(&self, index: usize) -> WorldVoxel<BlockTexture> {
+        match self.0[index] {
+            Value::Block(value) => value,
+            ref other => panic!("argument {} is {:?}, not a block", index, other),
+        }
+    }
+
+    pub(crate) fn text(&self, index: usize) -> &str {
+        match &self.0[index] {
+            Value::Text(value) => value,
+            other => panic!("argument {} is {:?}, not text", index, other),
+        }
+    }
+
+    pub(crate) fn path(&self, index: usize) -> &Path {
+        match &self.0[index] {
+            Value::Path(value) => value,
+            other => panic!("argument {} is {:?}, not a path", index, other),
+        }
+    }
+}
+
+/// Runs a command against the app's world, returning what to print.
+pub(crate) type CommandFn = fn(&mut World, &Args) -> Result<String, String>;
+
+#[derive(Clone)]
+pub(crate) struct Command {
+    pub(crate) name: &'static str,
+    pub(crate) args: Vec<Arg>,
+    pub(crate) help: &'static str,
+    pub(crate) run: CommandFn,
+}
+
+impl Command {
+    pub(crate) fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for arg in &self.args {
+            match (arg.kind, arg.optional) {
+                (ArgKind::Word(word), _) => write!(usage, " {}", word),
+                (_, false) => write!(usage, " <{}>", arg.name),
+                (_, true) => write!(usage, " [{}]", arg.name),
+            }
+            .unwrap();
+        }
+        usage
+    }
+
+    fn parse(&self, words: &[&str]) -> Result<Args, String> {
+        let required = self.args.iter().filter(|arg| !arg.optional).count();
+        if words.len() < required || words.len() > self.args.len() {
+            return Err(format!("usage: {}", self.usage()));
+        }
+        self.args
+            .iter()
+            .zip(words)
+            .map(|(arg, word)| arg.parse(word))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Args)
+    }
+}
+
+const HELP: &str = "help";
+
+/// The console commands. Scripts and tests run them the same way the console does, with
+/// `run_command` or `CommandRegistry::run`.
+#[derive(Resource, Clone)]
+pub(crate) struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self {
+            // Answered by the registry itself, which knows the other commands
+            commands: vec![Command {
+                name: HELP,
+                args: vec![Arg::new("command", ArgKind::Command).optional()],
+                help: "List the commands, or show how to use one.",
+                run: |_, _| Ok(String::new()),
+            }],
+        }
+    }
+}
+
+impl CommandRegistry {
+    /// Add a command, replacing the one with the same name.
+    pub(crate) fn register(&mut self, command: Command) {
+        self.commands.retain(|other| other.name != command.name);
+        self.commands.push(command);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.iter().map(|command| command.name)
+    }
+
+    pub(crate) fn parse(&self, line: &str) -> Result<(&Command, Args), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, words)) = words.split_first() else {
+            return Err("no command given".into());
+        };
+        let command = self
+            .get(name)
+            .ok_or_else(|| format!("unknown command '{}', try {}", name, HELP))?;
+        Ok((command, command.parse(words)?))
+    }
+
+    pub(crate) fn run(&self, world: &mut World, line: &str) -> Result<String, String> {
+        let (command, args) = self.parse(line)?;
+        if command.name == HELP {
+            return self.help(args.get(0).map(|_| args.text(0)));
+        }
+        (command.run)(world, &args)
+    }
+
+    /// The usage and help of a command, or of all of them.
+    pub(crate) fn help(&self, name: Option<&str>) -> Result<String, String> {
+        if let Some(name) = name {
+            let command = self
+                .get(name)
+                .ok_or_else(|| format!("unknown command '{}'", name))?;
+            return Ok(format!("{}\n  {}", command.usage(), command.help));
+        }
+        let mut commands: Vec<&Command> = self.commands.iter().collect();
+        commands.sort_by_key(|command| command.name);
+        Ok(commands
+            .iter()
+            .map(|command| format!("{} - {}", command.usage(), command.help))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// The lines `line` can be completed to, completing its last word.
+    pub(crate) fn complete(&self, line: &str) -> Vec<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (done, partial) = match words.split_last() {
+            Some((last, done)) if !line.ends_with(char::is_whitespace) => (done, *last),
+            _ => (words.as_slice(), ""),
+        };
+        let candidates = match done.split_first() {
+            None => self.names().map(str::to_string).collect(),
+            Some((name, args)) => self
+                .get(name)
+                .and_then(|command| command.args.get(args.len()))
+                .map_or(Vec::new(), |arg| arg.candidates(self)),
+        };
+        let mut lines: Vec<String> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .map(|candidate| {
+                done.iter()
+                    .copied()
+                    .chain([candidate.as_str()])
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        lines.sort();
+        lines
+    }
+}
+
+/// Run a command line with the registry of the app. The registry stays in the world, so
+/// commands running scripts can run commands themselves.
+pub(crate) fn run_command(world: &mut World, line: &str) -> Result<String, String> {
+    let registry = world.resource::<CommandRegistry>().clone();
+    registry.run(world, line)
+}
+
+/// The longest start all lines share.
+fn common_prefix(lines: &[String]) -> String {
+    let Some(first) = lines.first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for line in &lines[1..] {
+        let shared = prefix
+            .char_indices()
+            .zip(line.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(line.len()), |((index, _), _)| index);
+        prefix = &prefix[..shared];
+    }
+    prefix.to_string()
+}
+
+/// The drop-down console. Enter runs the line, Tab completes it, Up and Down go through the
+/// lines run before and Escape or the console binding close it.
+#[derive(Resource, Default)]
+pub(crate) struct Console {
+    open: bool,
+    input: String,
+    log: VecDeque<String>,
+    history: Vec<String>,
+    /// Index in the history of the line shown while going through it.
+    browsing: Option<usize>,
+    /// Lines entered this frame, run by `run_console_commands`.
+    pending: Vec<String>,
+}
+
+impl Console {
+    pub(crate) fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            self.log.push_back(line.to_string());
+        }
+        while self.log.len() > MAX_LOG {
+            self.log.pop_front();
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.browsing = None;
+        if line.trim().is_empty() {
+            return;
+        }
+        self.print(&format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.pending.push(line);
+    }
+
+    fn complete(&mut self, registry: &CommandRegistry) {
+        let lines = registry.complete(&self.input);
+        match lines.as_slice() {
+            [] => {}
+            [line] => self.input = format!("{} ", line),
+            _ => {
+                self.input = common_prefix(&lines);
+                let last_words: Vec<&str> = lines
+                    .iter()
+                    .filter_map(|line| line.split_whitespace().last())
+                    .collect();
+                self.print(&last_words.join("  "));
+            }
+        }
+    }
+
+    fn browse(&mut self, back: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        self.browsing = match (self.browsing, back) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index < last => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self
+            .browsing
+            .map_or(String::new(), |index| self.history[index].clone());
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct ConsoleText;
+
+pub(crate) fn setup_console(mut commands: Commands) {
+    commands.spawn((
+        ConsoleText,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.0),
+            left: Val::Px(0.0),
+            width: Val::Percent(100.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+/// While the console is open the keyboard types into it and no actions trigger.
+pub(crate) fn console_input(
+    mut typed: EventReader<KeyboardInput>,
+    keys: Res<ButtonInput<KeyCode>>,
+    map: Res<InputMap>,
+    registry: Res<CommandRegistry>,
+    mut actions: ResMut<Actions>,
+    mut console: ResMut<Console>,
+) {
+    if !console.open {
+        typed.clear();
+        if actions.just_pressed(Action::ToggleConsole) {
+            console.open = true;
+            actions.suspended = true;
+        }
+        return;
+    }
+    let toggled = map
+        .bindings(Action::ToggleConsole)
+        .iter()
+        .any(|binding| matches!(binding.button, Button::Key(key) if keys.just_pressed(key)));
+    if toggled || keys.just_pressed(KeyCode::Escape) {
+        typed.clear();
+        console.open = false;
+        actions.suspended = false;
+        return;
+    }
+    for event in typed.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(text) => console.input.push_str(text),
+            Key::Space => console.input.push(' '),
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Enter => console.submit(),
+            Key::Tab => console.complete(&registry),
+            Key::ArrowUp => console.browse(true),
+            Key::ArrowDown => console.browse(false),
+            _ => {}
+        }
+    }
+}
+
+/// Run the lines entered in the console, printing what they return.
+pub(crate) fn run_console_commands(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+    for line in pending {
+        let output = match run_command(world, &line) {
+            Ok(output) => output,
+            Err(err) => format!("error: {}", err),
+        };
+        world.resource_mut::<Console>().print(&output);
+    }
+}
+
+pub(crate) fn draw_console(
+    console: Res<Console>,
+    mut panel: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    let (mut text, mut visibility) = panel.single_mut();
+    *visibility = if console.open {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    let skip = console.log.len().saturating_sub(VISIBLE_LINES);
+    let mut shown = String::new();
+    for line in console.log.iter().skip(skip) {
+        let _ = writeln!(shown, "{}", line);
+    }
+    let _ = write!(shown, "> {}_", console.input);
+    text.0 = shown;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        registry.register(Command {
+            name: "fill",
+            args: vec![
+                Arg::new("x", ArgKind::Int),
+                Arg::new("block", ArgKind::Block),
+            ],
+            help: "Fill a voxel.",
+            run: |_, args| Ok(format!("{} {:?}", args.int(0), args.block(1))),
+        });
+        registry.register(Command {
+            name: "water",
+            args: vec![
+                Arg::new("spring", ArgKind::Word("spring")),
+                Arg::new("x", ArgKind::Float),
+            ],
+            help: "Water.",
+            run: |world, args| {
+                world.insert_resource(Time::<()>::default());
+                Ok(args.float(1).to_string())
+            },
+        });
+        registry
+    }
+
+    #[test]
+    fn run_parses_arguments() {
+        let sut = registry();
+        let mut world = World::new();
+        assert_eq!(
+            sut.run(&mut world, "fill -3 Lava"),
+            Ok("-3 Solid(Lava)".into())
+        );
+        assert_eq!(sut.run(&mut world, "water spring 1.5"), Ok("1.5".into()));
+        assert!(world.contains_resource::<Time>());
+        assert_eq!(
+            sut.run(&mut world, "fill a Lava"),
+            Err("invalid number 'a' for <x>".into())
+        );
+        assert_eq!(
+            sut.run(&mut world, "fill 1"),
+            Err("usage: fill <x> <block>".into())
+        );
+        assert_eq!(
+            sut.run(&mut world, "water well 1"),
+            Err("expected 'spring', found 'well'".into())
+        );
+        assert_eq!(
+            sut.run(&mut world, "tp 1 2 3"),
+            Err("unknown command 'tp', try help".into())
+        );
+    }
+
+    #[test]
+    fn help_lists_commands() {
+        let sut = registry();
+        let mut world = World::new();
+        assert_eq!(
+            sut.run(&mut world, "help water"),
+            Ok("water spring <x>\n  Water.".into())
+        );
+        let all = sut.run(&mut world, "help").unwrap();
+        assert_eq!(all.lines().count(), 3);
+        assert!(all.starts_with("fill <x> <block> - Fill a voxel.\nhelp [command]"));
+    }
+
+    #[test]
+    fn complete_last_word() {
+        let sut = registry();
+        assert_eq!(sut.complete(""), ["fill", "help", "water"]);
+        assert_eq!(sut.complete("w"), ["water"]);
+        assert_eq!(sut.complete("water "), ["water spring"]);
+        assert_eq!(
+            sut.complete("fill 1 Fen"),
+            [
+                "fill 1 FenceStone",
+                "fill 1 FenceStonePost",
+                "fill 1 FenceWood",
+                "fill 1 FenceWoodPost"
+            ]
+        );
+        assert_eq!(sut.complete("help f"), ["help fill"]);
+        assert!(sut.complete("fill ").is_empty());
+        assert_eq!(common_prefix(&sut.complete("fill 1 Fen")), "fill 1 Fence");
+    }
+
+    #[test]
+    fn names_go_to_dir() {
+        assert_eq!(
+            named_file("island", "assets/maps", "map"),
+            Path::new("assets/maps/island.map")
+        );
+        assert_eq!(
+            named_file("/tmp/island.map", "assets/maps", "map"),
+            Path::new("/tmp/island.map")
+        );
+    }
+
+    #[test]
+    fn history_browses_back_and_forth() {
+        let mut sut = Console::default();
+        for line in ["regen", "tp 0 0 0"] {
+            sut.input = line.into();
+            sut.submit();
+        }
+        assert_eq!(sut.pending, ["regen", "tp 0 0 0"]);
+        sut.browse(true);
+        sut.browse(true);
+        assert_eq!(sut.input, "regen");
+        sut.browse(false);
+        assert_eq!(sut.input, "tp 0 0 0");
+        sut.browse(false);
+        assert_eq!(sut.input, "");
+    }
+}
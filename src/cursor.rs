@@ -0,0 +1,220 @@
+use crate::blocks::{is_collidable, BlockProperties};
+use crate::editing::{self, WorldEditor};
+use crate::input::{self, Action, Actions};
+use crate::terrain::MyMainWorld;
+use crate::textures::BlockTexture;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+
+#[derive(Resource, Clone, Default)]
+pub(crate) struct VoxelTrace {
+    pub(crate) start: Option<Vec3>,
+    pub(crate) end: Vec3,
+    /// The voxel hit by the cursor ray.
+    pub(crate) hit: Option<IVec3>,
+}
+#[derive(Component)]
+pub(crate) struct CursorCube {
+    pub(crate) voxel_pos: IVec3,
+    pub(crate) voxel_mat: BlockTexture,
+}
+
+/// The cursor cube and whether the cursor edits the terrain.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CursorEditSettings {
+    pub color: Color,
+    /// Place, remove and pick blocks with the cursor, otherwise it only shows where it points.
+    pub editing: bool,
+}
+
+impl Default for CursorEditSettings {
+    fn default() -> Self {
+        Self {
+            color: Color::srgba_u8(124, 144, 255, 128),
+            editing: true,
+        }
+    }
+}
+
+/// The systems of the cursor, others that use the cursor run after `Trace`, edits of other tools
+/// after `Edit`.
+#[derive(SystemSet, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum CursorSet {
+    /// Finds the voxel the cursor points at.
+    Trace,
+    /// Tools that place their own blocks with the cursor. They consume the place action, so
+    /// the cursor doesn't place a block as well.
+    Tools,
+    /// Places and removes the block at the cursor.
+    Edit,
+}
+
+/// A cube that follows the voxel under the cursor, where blocks are placed.
+/// Needs the terrain and water plugins and a camera with a `VoxelWorldCamera<MyMainWorld>`.
+#[derive(Default)]
+pub struct CursorEditPlugin {
+    pub settings: CursorEditSettings,
+}
+
+impl Plugin for CursorEditPlugin {
+    fn build(&self, app: &mut App) {
+        input::add_actions(app);
+        editing::add_editing(app);
+        app.insert_resource(self.settings)
+            .init_resource::<VoxelTrace>()
+            .configure_sets(
+                Update,
+                (CursorSet::Trace, CursorSet::Tools, CursorSet::Edit).chain(),
+            )
+            .add_systems(Startup, spawn_cursor_cube)
+            .add_systems(Update, update_cursor_cube.in_set(CursorSet::Trace))
+            .add_systems(
+                Update,
+                (place_cursor_block, remove_cursor_block)
+                    .chain()
+                    .in_set(CursorSet::Edit)
+                    .run_if(|settings: Res<CursorEditSettings>| settings.editing),
+            );
+    }
+}
+
+fn spawn_cursor_cube(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<CursorEditSettings>,
+) {
+    commands.spawn((
+        Transform::from_xyz(0.0, -10.0, 0.0),
+        Mesh3d(meshes.add(Mesh::from(Cuboid {
+            half_size: Vec3::splat(0.5),
+        }))),
+        MeshMaterial3d(materials.add(settings.color)),
+        CursorCube {
+            voxel_pos: IVec3::new(0, -10, 0),
+            voxel_mat: BlockTexture::FullBrick,
+        },
+    ));
+}
+
+/// Cast a ray from the cursor every frame, so the target follows the camera and edits.
+fn update_cursor_cube(
+    voxel_world_raycast: VoxelWorld<MyMainWorld>,
+    mut trace: ResMut<VoxelTrace>,
+    camera_info: Query<(&Camera, &GlobalTransform), With<VoxelWorldCamera<MyMainWorld>>>,
+    windows: Query<&Window>,
+    mut cursor_cube: Query<(&mut Transform, &mut CursorCube)>,
+) {
+    let Some(cursor) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        // Outside the window, there is nothing to pick or remove
+        trace.hit = None;
+        return;
+    };
+    // Get a ray from the cursor position into the world
+    let (camera, cam_gtf) = camera_info.single();
+    let Ok(ray) = camera.viewport_to_world(cam_gtf, cursor) else {
+        return;
+    };
+
+    // Liquids and other non-collidable blocks can't be targeted, the cursor goes through them
+    let Some(result) = voxel_world_raycast.raycast(ray, &|(_pos, vox)| is_collidable(vox)) else {
+        // Pointing at the sky, there is nothing to pick or remove
+        trace.hit = None;
+        return;
+    };
+    let (mut transform, mut cursor_cube) = cursor_cube.single_mut();
+
+    trace.hit = Some(result.position.as_ivec3());
+
+    // Camera could end up inside geometry - in that case just ignore the trace
+    if let Some(normal) = result.normal {
+        // Move the cursor cube to the position of the voxel we hit
+        let voxel_pos = result.position + normal;
+        let target = voxel_world_raycast.get_voxel(voxel_pos.as_ivec3());
+        if !BlockProperties::of(target).replaceable() {
+            return;
+        }
+        transform.translation = voxel_pos + Vec3::splat(VOXEL_SIZE / 2.);
+        cursor_cube.voxel_pos = voxel_pos.as_ivec3();
+        // Update current trace end to the cursor cube position
+        trace.end = transform.translation;
+    }
+}
+
+fn place_cursor_block(
+    mut editor: WorldEditor,
+    actions: Res<Actions>,
+    cursor_cube: Query<&CursorCube>,
+) {
+    if !actions.just_pressed(Action::Place) {
+        return;
+    }
+    let cursor_cube = cursor_cube.single();
+    let target = editor.get_voxel(cursor_cube.voxel_pos);
+    if BlockProperties::of(target).replaceable() {
+        editor.set_voxel(
+            cursor_cube.voxel_pos,
+            WorldVoxel::Solid(cursor_cube.voxel_mat),
+        );
+    }
+}
+
+/// Remove the block hit by the cursor.
+/// Seconds the remove action is held to break a block of hardness 1.
+const BREAK_SECONDS: f32 = 0.25;
+
+/// Seconds the remove action is held to break `voxel`.
+fn break_seconds(voxel: WorldVoxel<BlockTexture>) -> f32 {
+    BlockProperties::of(voxel).hardness * BREAK_SECONDS
+}
+
+/// Remove the block at the cursor once the remove action has been held on it long enough.
+fn remove_cursor_block(
+    mut editor: WorldEditor,
+    actions: Res<Actions>,
+    trace: Res<VoxelTrace>,
+    time: Res<Time>,
+    mut breaking: Local<Option<(IVec3, f32)>>,
+) {
+    let Some(hit) = trace.hit.filter(|_| actions.pressed(Action::Remove)) else {
+        *breaking = None;
+        return;
+    };
+    let held = match *breaking {
+        Some((pos, held)) if pos == hit => held + time.delta_secs(),
+        _ => time.delta_secs(),
+    };
+    if held >= break_seconds(editor.get_voxel(hit)) {
+        editor.set_voxel(hit, WorldVoxel::Air);
+        *breaking = None;
+    } else {
+        *breaking = Some((hit, held));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harder_blocks_take_longer_to_break() {
+        let solid = WorldVoxel::Solid;
+        assert_eq!(
+            break_seconds(solid(BlockTexture::StoneBrick)),
+            BREAK_SECONDS
+        );
+        assert!(
+            break_seconds(solid(BlockTexture::Leaves))
+                < break_seconds(solid(BlockTexture::DirtBrick))
+        );
+        assert!(
+            break_seconds(solid(BlockTexture::RockBrick))
+                > break_seconds(solid(BlockTexture::StoneBrick))
+        );
+        assert_eq!(break_seconds(WorldVoxel::Air), 0.0);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::input::{Action, Actions};
+use crate::lighting::CHUNK_SIZE;
+use crate::{MyMainWorld, WaterWorld};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_voxel_world::prelude::*;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Number of the latest meshing times the average is taken over.
+const MESH_TIME_SAMPLES: usize = 100;
+
+/// Toggles the chunk boundaries and chunk stats of both worlds.
+#[derive(Resource, Default)]
+pub(crate) struct DebugOverlay {
+    pub(crate) visible: bool,
+}
+
+/// Chunk counts of a voxel world, kept up to date while the overlay is hidden as well.
+#[derive(Resource)]
+pub(crate) struct ChunkStats<C> {
+    /// Chunk entities that exist right now.
+    spawned: usize,
+    /// Chunks being meshed, with the time meshing started.
+    queued: HashMap<Entity, Duration>,
+    /// Meshing tasks finished so far.
+    meshed: usize,
+    /// Chunks despawned so far.
+    despawned: usize,
+    mesh_times: VecDeque<Duration>,
+    _world: PhantomData<C>,
+}
+
+impl<C> Default for ChunkStats<C> {
+    fn default() -> Self {
+        Self {
+            spawned: 0,
+            queued: HashMap::new(),
+            meshed: 0,
+            despawned: 0,
+            mesh_times: VecDeque::new(),
+            _world: PhantomData,
+        }
+    }
+}
+
+impl<C> ChunkStats<C> {
+    fn mesh_started(&mut self, chunk: Entity, now: Duration) {
+        self.queued.insert(chunk, now);
+    }
+
+    fn mesh_finished(&mut self, chunk: Entity, now: Duration) {
+        self.meshed += 1;
+        if let Some(started) = self.queued.remove(&chunk) {
+            self.mesh_times.push_back(now.saturating_sub(started));
+            if self.mesh_times.len() > MESH_TIME_SAMPLES {
+                self.mesh_times.pop_front();
+            }
+        }
+    }
+
+    fn chunk_despawned(&mut self, chunk: Entity) {
+        self.despawned += 1;
+        self.queued.remove(&chunk);
+    }
+
+    fn average_mesh_time(&self) -> Duration {
+        if self.mesh_times.is_empty() {
+            return Duration::ZERO;
+        }
+        self.mesh_times.iter().sum::<Duration>() / self.mesh_times.len() as u32
+    }
+
+    fn summary(&self, name: &str) -> String {
+        format!(
+            "{}: spawned {} queued {} meshed {} despawned {} avg mesh {:.1} ms",
+            name,
+            self.spawned,
+            self.queued.len(),
+            self.meshed,
+            self.despawned,
+            self.average_mesh_time().as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Meshing time is from `bevy_voxel_world` starting the meshing task of a chunk to the chunk
+/// being spawned with the result.
+pub(crate) fn track_chunks<C: VoxelWorldConfig>(
+    time: Res<Time<Real>>,
+    mut stats: ResMut<ChunkStats<C>>,
+    mut remeshing: EventReader<ChunkWillRemesh<C>>,
+    mut spawning: EventReader<ChunkWillSpawn<C>>,
+    mut despawning: EventReader<ChunkWillDespawn<C>>,
+    chunks: Query<(), With<Chunk<C>>>,
+) {
+    let now = time.elapsed();
+    for ev in remeshing.read() {
+        stats.mesh_started(ev.entity, now);
+    }
+    for ev in spawning.read() {
+        stats.mesh_finished(ev.entity, now);
+    }
+    for ev in despawning.read() {
+        stats.chunk_despawned(ev.entity);
+    }
+    let spawned = chunks.iter().count();
+    if stats.spawned != spawned {
+        stats.spawned = spawned;
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct DebugOverlayText;
+
+pub(crate) fn setup_debug_overlay(mut commands: Commands) {
+    commands.spawn((
+        DebugOverlayText,
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        Visibility::Hidden,
+    ));
+}
+
+pub(crate) fn toggle_debug_overlay(
+    actions: Res<Actions>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut text: Query<&mut Visibility, With<DebugOverlayText>>,
+) {
+    if !actions.just_pressed(Action::ToggleOverlay) {
+        return;
+    }
+    overlay.visible = !overlay.visible;
+    *text.single_mut() = if overlay.visible {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+}
+
+pub(crate) fn update_debug_overlay(
+    overlay: Res<DebugOverlay>,
+    terrain: Res<ChunkStats<MyMainWorld>>,
+    water: Res<ChunkStats<WaterWorld>>,
+    mut text: Query<&mut Text, With<DebugOverlayText>>,
+) {
+    if !overlay.visible {
+        return;
+    }
+    text.single_mut().0 = format!("{}\n{}", terrain.summary("Terrain"), water.summary("Water"));
+}
+
+/// Outline every chunk of the world while the overlay is shown.
+pub(crate) fn draw_chunk_boundaries<C: VoxelWorldConfig>(
+    color: Color,
+) -> impl Fn(Res<DebugOverlay>, Query<&Chunk<C>>, Gizmos) {
+    move |overlay, chunks, mut gizmos| {
+        if !overlay.visible {
+            return;
+        }
+        for chunk in chunks.iter() {
+            let size = CHUNK_SIZE as f32;
+            let min = chunk.position.as_vec3() * size;
+            gizmos.cuboid(
+                Transform::from_translation(min + Vec3::splat(size / 2.0))
+                    .with_scale(Vec3::splat(size)),
+                color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_time_from_start_to_spawn() {
+        let mut sut = ChunkStats::<()>::default();
+        let chunk = Entity::from_raw(1);
+        sut.mesh_started(chunk, Duration::from_millis(10));
+        sut.mesh_started(Entity::from_raw(2), Duration::from_millis(10));
+        assert_eq!(sut.queued.len(), 2);
+        sut.mesh_finished(chunk, Duration::from_millis(14));
+        sut.chunk_despawned(Entity::from_raw(2));
+        assert_eq!(sut.queued.len(), 0);
+        assert_eq!(sut.meshed, 1);
+        assert_eq!(sut.despawned, 1);
+        assert_eq!(sut.average_mesh_time(), Duration::from_millis(4));
+    }
+}
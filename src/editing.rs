@@ -0,0 +1,192 @@
+use crate::blocks::is_liquid;
+use crate::connected::connected_updates;
+use crate::textures::BlockTexture;
+use crate::water::BLUE;
+use crate::{MyMainWorld, WaterWorld};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_voxel_world::prelude::*;
+use std::sync::{Arc, OnceLock};
+
+/// A voxel was changed by an edit. `VoxelChanged` is for the terrain world, `WaterChanged` for
+/// the water world.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct VoxelChanged<M: Copy + Send + Sync + 'static = BlockTexture> {
+    pub(crate) pos: IVec3,
+    pub(crate) before: WorldVoxel<M>,
+    pub(crate) after: WorldVoxel<M>,
+    /// Written by undo or redo, rather than being a new edit.
+    pub(crate) replay: bool,
+}
+
+pub(crate) type WaterChanged = VoxelChanged<u8>;
+
+/// Every voxel changed by an edit since the map was generated or loaded, with its value now.
+/// Saved with the map, the terrain itself is generated again from it.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub(crate) struct EditedVoxels {
+    pub(crate) terrain: HashMap<IVec3, WorldVoxel<BlockTexture>>,
+    pub(crate) water: HashMap<IVec3, WorldVoxel<u8>>,
+}
+
+type VoxelFn<M> = Arc<dyn Fn(IVec3) -> WorldVoxel<M> + Send + Sync>;
+
+/// The voxels edits wrote this frame, until the worlds flush their write buffers.
+/// `VoxelWorld::get_voxel` copies the whole write buffer on every call and finds the first write
+/// to a position in it, edits read their own writes from here instead and look up the rest in a
+/// copy of the worlds taken once per frame.
+#[derive(Resource, Default)]
+pub(crate) struct PendingWrites {
+    terrain: HashMap<IVec3, WorldVoxel<BlockTexture>>,
+    water: HashMap<IVec3, WorldVoxel<u8>>,
+    terrain_world: OnceLock<VoxelFn<BlockTexture>>,
+    water_world: OnceLock<VoxelFn<u8>>,
+}
+
+fn clear_pending_writes(mut pending: ResMut<PendingWrites>) {
+    *pending = PendingWrites::default();
+}
+
+/// Resources and events of `WorldEditor`. Every plugin that edits calls it, only the first call
+/// adds them.
+pub(crate) fn add_editing(app: &mut App) {
+    if app.world().contains_resource::<PendingWrites>() {
+        return;
+    }
+    app.init_resource::<PendingWrites>()
+        .init_resource::<EditedVoxels>()
+        .add_event::<VoxelChanged>()
+        .add_event::<WaterChanged>()
+        // The worlds flush their write buffers in `PreUpdate`
+        .add_systems(First, clear_pending_writes);
+}
+
+/// Write access to the terrain and water worlds for edits. Keeps connected blocks joined, puts
+/// liquids in the water world and reports every changed voxel as an event.
+#[derive(SystemParam)]
+pub(crate) struct WorldEditor<'w> {
+    voxel_world: VoxelWorld<'w, MyMainWorld>,
+    water_world: VoxelWorld<'w, WaterWorld>,
+    changes: EventWriter<'w, VoxelChanged>,
+    water_changes: EventWriter<'w, WaterChanged>,
+    edited: ResMut<'w, EditedVoxels>,
+    pending: ResMut<'w, PendingWrites>,
+}
+
+impl WorldEditor<'_> {
+    pub(crate) fn get_voxel(&self, pos: IVec3) -> WorldVoxel<BlockTexture> {
+        match self.pending.terrain.get(&pos) {
+            Some(voxel) => *voxel,
+            None => self
+                .pending
+                .terrain_world
+                .get_or_init(|| self.voxel_world.get_voxel_fn())(pos),
+        }
+    }
+
+    pub(crate) fn set_voxel(&mut self, pos: IVec3, voxel: WorldVoxel<BlockTexture>) {
+        if is_liquid(voxel) {
+            self.write(pos, WorldVoxel::Air, false);
+            self.write_water(pos, WorldVoxel::Solid(BLUE), false);
+        } else {
+            self.write(pos, voxel, false);
+            // Blocks push the water out, clearing a voxel clears the water too
+            if matches!(self.get_water(pos), WorldVoxel::Solid(_)) {
+                self.write_water(pos, WorldVoxel::Air, false);
+            }
+        }
+        // Neighbours written earlier this frame are read as they are now
+        for (pos, voxel) in connected_updates(&|pos| self.get_voxel(pos), pos) {
+            self.write(pos, voxel, false);
+        }
+    }
+
+    pub(crate) fn get_water(&self, pos: IVec3) -> WorldVoxel<u8> {
+        match self.pending.water.get(&pos) {
+            Some(voxel) => *voxel,
+            None => self
+                .pending
+                .water_world
+                .get_or_init(|| self.water_world.get_voxel_fn())(pos),
+        }
+    }
+
+    /// Put back a terrain voxel exactly as it was, for undo and redo or loading edits.
+    pub(crate) fn restore(&mut self, pos: IVec3, voxel: WorldVoxel<BlockTexture>) {
+        self.write(pos, voxel, true);
+    }
+
+    /// Put back a water voxel exactly as it was, for undo and redo or loading edits.
+    pub(crate) fn restore_water(&mut self, pos: IVec3, voxel: WorldVoxel<u8>) {
+        self.write_water(pos, voxel, true);
+    }
+
+    fn write(&mut self, pos: IVec3, after: WorldVoxel<BlockTexture>, replay: bool) {
+        let before = self.get_voxel(pos);
+        // Replays always write, chunks of a map that was just replaced still hold its voxels
+        if before == after && !replay {
+            return;
+        }
+        self.voxel_world.set_voxel(pos, after);
+        self.pending.terrain.insert(pos, after);
+        self.edited.terrain.insert(pos, after);
+        self.changes.send(VoxelChanged {
+            pos,
+            before,
+            after,
+            replay,
+        });
+    }
+
+    fn write_water(&mut self, pos: IVec3, after: WorldVoxel<u8>, replay: bool) {
+        let before = self.get_water(pos);
+        if before == after && !replay {
+            return;
+        }
+        self.water_world.set_voxel(pos, after);
+        self.pending.water.insert(pos, after);
+        self.edited.water.insert(pos, after);
+        self.water_changes.send(WaterChanged {
+            pos,
+            before,
+            after,
+            replay,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_of_fences_in_one_frame() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            VoxelWorldPlugin::<MyMainWorld>::minimal(),
+            VoxelWorldPlugin::<WaterWorld>::minimal(),
+        ));
+        add_editing(&mut app);
+        // Chunks spawn around a camera
+        app.add_systems(Startup, |mut commands: Commands| {
+            commands.spawn((
+                Camera3d::default(),
+                VoxelWorldCamera::<MyMainWorld>::default(),
+                VoxelWorldCamera::<WaterWorld>::default(),
+            ));
+        });
+        app.add_systems(Update, |mut editor: WorldEditor| {
+            for x in 0..3 {
+                let pos = IVec3::new(x, 100, 0);
+                editor.set_voxel(pos, WorldVoxel::Solid(BlockTexture::FenceWood));
+            }
+            let row: Vec<_> = (0..3)
+                .map(|x| editor.get_voxel(IVec3::new(x, 100, 0)))
+                .collect();
+            assert_eq!(row, vec![WorldVoxel::Solid(BlockTexture::FenceWood); 3]);
+        });
+        app.update();
+    }
+}
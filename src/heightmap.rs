@@ -0,0 +1,123 @@
+use crate::map::{Map, MapNode, NodeType};
+use bevy::math::{IVec3, UVec2};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Heights are stored as grey levels, this level is height 0.
+const SEA_LEVEL: i32 = 128;
+
+/// The heights of a map as grey levels, a pixel per column and a row of pixels for every z.
+pub fn encode(map: &Map) -> Vec<u8> {
+    let (min, extent) = (map.min_corner(), map.extent());
+    let mut pixels = Vec::with_capacity((extent.x * extent.y) as usize);
+    for z in min.y..min.y + extent.y as i32 {
+        for x in min.x..min.x + extent.x as i32 {
+            let height = map.get(IVec3::new(x, 0, z)).unwrap_or_default().height;
+            pixels.push((height as i32 + SEA_LEVEL) as u8);
+        }
+    }
+    pixels
+}
+
+/// A map with the heights of grey levels, each column gets the surface generated terrain has
+/// at its height.
+pub fn decode(extent: UVec2, pixels: &[u8]) -> Result<Map, String> {
+    if pixels.len() != (extent.x * extent.y) as usize {
+        return Err(format!(
+            "expected {} pixels for a {}x{} heightmap, got {}",
+            extent.x * extent.y,
+            extent.x,
+            extent.y,
+            pixels.len()
+        ));
+    }
+    let rows = (0..extent.x as usize)
+        .map(|x| {
+            (0..extent.y as usize)
+                .map(|z| {
+                    let height = (pixels[z * extent.x as usize + x] as i32 - SEA_LEVEL) as i8;
+                    MapNode {
+                        surface_type: NodeType::at_height(height),
+                        height,
+                        moisture: 0,
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Map::from_rows(rows)
+}
+
+/// The size and grey levels of a PNG image, colors are averaged to grey.
+pub fn read_png(file: &Path) -> Result<(UVec2, Vec<u8>), String> {
+    let error = |err: &dyn std::fmt::Display| format!("can't read {}: {}", file.display(), err);
+    let mut decoder = png::Decoder::new(File::open(file).map_err(|err| error(&err))?);
+    // Palettes and low bit depths become 8 bit channels
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| error(&err))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).map_err(|err| error(&err))?;
+    let channels = frame.color_type.samples();
+    let grey = buffer[..frame.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| match pixel {
+            [r, g, b, ..] => ((*r as u32 + *g as u32 + *b as u32) / 3) as u8,
+            [grey, ..] => *grey,
+            [] => 0,
+        })
+        .collect();
+    Ok((UVec2::new(frame.width, frame.height), grey))
+}
+
+/// Write pixels of grey levels or RGB colors to a PNG image.
+pub fn write_png(
+    file: &Path,
+    extent: UVec2,
+    color: png::ColorType,
+    pixels: &[u8],
+) -> Result<(), String> {
+    let error = |err: &dyn std::fmt::Display| format!("can't write {}: {}", file.display(), err);
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| error(&err))?;
+    }
+    let writer = BufWriter::new(File::create(file).map_err(|err| error(&err))?);
+    let mut encoder = png::Encoder::new(writer, extent.x, extent.y);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .map_err(|err| error(&err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heights_round_trip() {
+        let map = Map::test_map();
+        let pixels = encode(&map);
+        // The first row of pixels is the row of columns with the lowest z
+        assert_eq!(pixels[0], 128 + 8);
+        assert_eq!(pixels[1], 128 + 5);
+        let sut = decode(map.extent(), &pixels).unwrap();
+        let heights = |map: &Map| -> Vec<Vec<i8>> {
+            map.map
+                .iter()
+                .map(|row| row.iter().map(|node| node.height).collect())
+                .collect()
+        };
+        assert_eq!(heights(&sut), heights(&map));
+        assert_eq!(sut.get(IVec3::ZERO).unwrap().surface_type, NodeType::Grass);
+    }
+
+    #[test]
+    fn decode_checks_size() {
+        assert_eq!(
+            decode(UVec2::new(2, 2), &[128; 3]),
+            Err("expected 4 pixels for a 2x2 heightmap, got 3".into())
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::editing::{VoxelChanged, WaterChanged, WorldEditor};
+use crate::input::{Action, Actions};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+pub(crate) const DEFAULT_HISTORY_SIZE: usize = 100;
+
+/// One reversible edit, all voxels changed by a single placement, fill or brush stroke.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Edit {
+    terrain: Vec<VoxelChanged>,
+    water: Vec<WaterChanged>,
+}
+
+impl Edit {
+    fn is_empty(&self) -> bool {
+        self.terrain.is_empty() && self.water.is_empty()
+    }
+
+    /// The edit that takes the worlds back to before this one.
+    fn inverse(&self) -> Edit {
+        fn invert<M: Copy + Send + Sync + 'static>(
+            changes: &[VoxelChanged<M>],
+        ) -> Vec<VoxelChanged<M>> {
+            changes
+                .iter()
+                .rev()
+                .map(|change| VoxelChanged {
+                    before: change.after,
+                    after: change.before,
+                    ..*change
+                })
+                .collect()
+        }
+        Edit {
+            terrain: invert(&self.terrain),
+            water: invert(&self.water),
+        }
+    }
+
+    fn apply(&self, editor: &mut WorldEditor) {
+        for change in &self.terrain {
+            editor.restore(change.pos, change.after);
+        }
+        for change in &self.water {
+            editor.restore_water(change.pos, change.after);
+        }
+    }
+}
+
+/// Undo and redo stacks of the edits to the worlds. Only the latest `max_size` edits are kept.
+#[derive(Resource, Debug)]
+pub(crate) struct EditHistory {
+    pub(crate) max_size: usize,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl EditHistory {
+    pub(crate) fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Record a new edit, which makes the undone edits unreachable.
+    pub(crate) fn push(&mut self, edit: Edit) {
+        if edit.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(edit);
+        while self.undo.len() > self.max_size {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Forget all edits, for when the worlds they were made to are gone.
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// The edit to apply to undo the latest edit.
+    fn undo(&mut self) -> Option<Edit> {
+        let edit = self.undo.pop_back()?;
+        let inverse = edit.inverse();
+        self.redo.push(edit);
+        Some(inverse)
+    }
+
+    /// The edit to apply to redo the latest undone edit.
+    fn redo(&mut self) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        self.undo.push_back(edit.clone());
+        Some(edit)
+    }
+}
+
+/// Everything changed in a frame is one edit.
+pub(crate) fn record_edits(
+    mut history: ResMut<EditHistory>,
+    mut changes: EventReader<VoxelChanged>,
+    mut water_changes: EventReader<WaterChanged>,
+) {
+    let edit = Edit {
+        terrain: changes
+            .read()
+            .filter(|change| !change.replay)
+            .copied()
+            .collect(),
+        water: water_changes
+            .read()
+            .filter(|change| !change.replay)
+            .copied()
+            .collect(),
+    };
+    history.push(edit);
+}
+
+/// Undo the latest edit, or redo the latest undone one.
+pub(crate) fn undo_redo(
+    actions: Res<Actions>,
+    mut history: ResMut<EditHistory>,
+    mut editor: WorldEditor,
+) {
+    let edit = if actions.just_pressed(Action::Redo) {
+        history.redo()
+    } else if actions.just_pressed(Action::Undo) {
+        history.undo()
+    } else {
+        None
+    };
+    if let Some(edit) = edit {
+        edit.apply(&mut editor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::BlockTexture;
+    use bevy_voxel_world::prelude::WorldVoxel;
+
+    fn placed(x: i32) -> Edit {
+        Edit {
+            terrain: vec![VoxelChanged {
+                pos: IVec3::new(x, 0, 0),
+                before: WorldVoxel::Air,
+                after: WorldVoxel::Solid(BlockTexture::StoneBrick),
+                replay: false,
+            }],
+            water: vec![],
+        }
+    }
+
+    #[test]
+    fn undo_inverts_and_redo_repeats() {
+        let mut sut = EditHistory::new(10);
+        sut.push(placed(0));
+        let undo = sut.undo().unwrap();
+        assert_eq!(undo.terrain[0].after, WorldVoxel::Air);
+        assert_eq!(sut.redo(), Some(placed(0)));
+        assert_eq!(sut.redo(), None);
+    }
+
+    #[test]
+    fn new_edit_drops_redo() {
+        let mut sut = EditHistory::new(10);
+        sut.push(placed(0));
+        sut.undo();
+        sut.push(placed(1));
+        assert_eq!(sut.redo(), None);
+        assert_eq!(sut.undo().unwrap().terrain[0].pos, IVec3::new(1, 0, 0));
+        assert_eq!(sut.undo(), None);
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut sut = EditHistory::new(2);
+        for x in 0..5 {
+            sut.push(placed(x));
+        }
+        sut.push(Edit::default());
+        assert!(sut.undo().is_some());
+        assert!(sut.undo().is_some());
+        assert_eq!(sut.undo(), None);
+    }
+}
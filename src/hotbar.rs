@@ -0,0 +1,340 @@
+use crate::camera::CameraMode;
+use crate::cursor::{CursorCube, VoxelTrace};
+use crate::input::{Action, Actions};
+use crate::texture_pack::TexturePackApplied;
+use crate::textures::BlockTexture;
+use crate::MyMainWorld;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension};
+use bevy::ui::RelativeCursorPosition;
+use bevy_voxel_world::prelude::*;
+
+/// Blocks on the hotbar. Connected blocks only list one variant, the others are picked by
+/// their neighbours.
+const HOTBAR_BLOCKS: [BlockTexture; 16] = [
+    BlockTexture::FullBrick,
+    BlockTexture::GrassBrick,
+    BlockTexture::DirtBrick,
+    BlockTexture::SandBrick,
+    BlockTexture::GravelBrick,
+    BlockTexture::StoneBrick,
+    BlockTexture::RockBrick,
+    BlockTexture::SnowyBrick,
+    BlockTexture::WaterBrick,
+    BlockTexture::Leaves,
+    BlockTexture::FenceWood,
+    BlockTexture::FenceStone,
+    BlockTexture::GlassFrame,
+    BlockTexture::TrackNorthSouth,
+    BlockTexture::Lava,
+    BlockTexture::DiamondOre,
+];
+
+const SLOT_SIZE: f32 = 44.0;
+const SELECTED_BORDER: Color = Color::srgb(1.0, 0.9, 0.4);
+const SLOT_BORDER: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+
+/// The blocks to pick from and the one placed by the cursor.
+#[derive(Resource)]
+pub(crate) struct Hotbar {
+    pub(crate) blocks: Vec<BlockTexture>,
+    pub(crate) selected: usize,
+    /// Top tile of every block, cut out of the atlas of the active texture pack.
+    icons: Vec<Handle<Image>>,
+    atlas: Option<Handle<Image>>,
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        Self {
+            blocks: HOTBAR_BLOCKS.to_vec(),
+            selected: 0,
+            icons: Vec::new(),
+            atlas: None,
+        }
+    }
+}
+
+impl Hotbar {
+    pub(crate) fn selected_block(&self) -> BlockTexture {
+        self.blocks[self.selected]
+    }
+
+    /// Select the block, adding it to the end of the hotbar if it isn't on it. Connected blocks
+    /// select the variant on the hotbar.
+    pub(crate) fn select(&mut self, block: BlockTexture) {
+        let group = block.connection_group();
+        self.selected = match self
+            .blocks
+            .iter()
+            .position(|b| *b == block || (group.is_some() && b.connection_group() == group))
+        {
+            Some(index) => index,
+            None => {
+                self.blocks.push(block);
+                self.blocks.len() - 1
+            }
+        };
+    }
+
+    /// Move the selection by `steps`, wrapping around at the ends.
+    fn scroll(&mut self, steps: i32) {
+        let len = self.blocks.len() as i32;
+        self.selected = (self.selected as i32 + steps).rem_euclid(len) as usize;
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct HotbarPanel;
+
+#[derive(Component)]
+pub(crate) struct HotbarSlot(usize);
+
+#[derive(Component)]
+pub(crate) struct HotbarIcon(usize);
+
+pub(crate) fn setup_hotbar(mut commands: Commands, hotbar: Res<Hotbar>) {
+    commands
+        .spawn((
+            HotbarPanel,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+        ))
+        .with_children(|bar| {
+            for index in 0..hotbar.blocks.len() {
+                spawn_slot(bar, index, Handle::default());
+            }
+        });
+}
+
+fn spawn_slot(bar: &mut ChildBuilder, index: usize, icon: Handle<Image>) {
+    bar.spawn((
+        HotbarSlot(index),
+        Node {
+            width: Val::Px(SLOT_SIZE),
+            height: Val::Px(SLOT_SIZE),
+            border: UiRect::all(Val::Px(3.0)),
+            ..default()
+        },
+        BorderColor(SLOT_BORDER),
+        BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.6)),
+        // The RTS camera doesn't scroll while the cursor is on the hotbar
+        RelativeCursorPosition::default(),
+    ))
+    .with_children(|slot| {
+        slot.spawn((
+            HotbarIcon(index),
+            ImageNode::new(icon),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Copy a single layer out of an array texture.
+fn layer_image(atlas: &Image, layer: u32) -> Option<Image> {
+    let size = atlas.texture_descriptor.size;
+    let format = atlas.texture_descriptor.format;
+    let layer_len = atlas.data.len() / size.depth_or_array_layers as usize;
+    let start = layer as usize * layer_len;
+    let data = atlas.data.get(start..start + layer_len)?.to_vec();
+    Some(Image::new(
+        Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::default(),
+    ))
+}
+
+/// Cut new icons out of the atlas whenever a texture pack is applied or blocks are added.
+pub(crate) fn update_hotbar_icons(
+    mut applied: EventReader<TexturePackApplied>,
+    mut hotbar: ResMut<Hotbar>,
+    world: Res<MyMainWorld>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(TexturePackApplied { atlas }) = applied.read().last() {
+        hotbar.atlas = Some(atlas.clone());
+    } else if hotbar.icons.len() == hotbar.blocks.len() {
+        return;
+    }
+    let Some(atlas) = hotbar
+        .atlas
+        .as_ref()
+        .and_then(|atlas| images.get(atlas))
+        .cloned()
+    else {
+        return;
+    };
+    let pack = world.texture_pack.read().unwrap().clone();
+    hotbar.icons = hotbar
+        .blocks
+        .iter()
+        .map(|block| {
+            let [top, _, _] = pack.index(*block);
+            layer_image(&atlas, top)
+                .map(|icon| images.add(icon))
+                .unwrap_or_default()
+        })
+        .collect();
+}
+
+/// The slot actions pick one of the first ten blocks, the next and previous block actions step
+/// through all of them, as does the scroll wheel while the camera doesn't take it.
+pub(crate) fn select_hotbar_block(
+    actions: Res<Actions>,
+    mut wheel: EventReader<MouseWheel>,
+    mut hotbar: ResMut<Hotbar>,
+    mode: Option<Res<CameraMode>>,
+) {
+    if let Some(index) = Action::SLOTS
+        .iter()
+        .position(|slot| actions.just_pressed(*slot))
+    {
+        if index < hotbar.blocks.len() {
+            hotbar.selected = index;
+        }
+    }
+    let scrolled: f32 = wheel.read().map(|ev| ev.y).sum();
+    if scrolled != 0.0 && mode.is_none_or(|mode| !mode.uses_wheel(&actions)) {
+        hotbar.scroll(-scrolled.signum() as i32);
+    }
+    if actions.just_pressed(Action::NextBlock) {
+        hotbar.scroll(1);
+    } else if actions.just_pressed(Action::PreviousBlock) {
+        hotbar.scroll(-1);
+    }
+}
+
+/// Pick the block hit by the cursor as the one to place.
+pub(crate) fn pick_cursor_block(
+    voxel_world: VoxelWorld<MyMainWorld>,
+    world: Res<MyMainWorld>,
+    actions: Res<Actions>,
+    trace: Res<VoxelTrace>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    if !actions.just_pressed(Action::Pick) {
+        return;
+    }
+    let Some(hit) = trace.hit else {
+        return;
+    };
+    // Voxels of chunks that aren't loaded are unset, the map still knows the column
+    let block = match voxel_world.get_voxel(hit) {
+        WorldVoxel::Solid(block) => Some(block),
+        _ => world
+            .static_map
+            .get(hit)
+            .map(|node| node.surface_type.block()),
+    };
+    if let Some(block) = block {
+        hotbar.select(block);
+    }
+}
+
+/// Show the selection on the hotbar and on the cursor cube.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_hotbar(
+    mut commands: Commands,
+    hotbar: Res<Hotbar>,
+    panel: Query<Entity, With<HotbarPanel>>,
+    mut slots: Query<(&HotbarSlot, &mut BorderColor)>,
+    mut icons: Query<(&HotbarIcon, &mut ImageNode)>,
+    mut cursor_cube: Query<(&mut CursorCube, &MeshMaterial3d<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !hotbar.is_changed() {
+        return;
+    }
+    let slot_count = slots.iter().count();
+    if slot_count < hotbar.blocks.len() {
+        commands.entity(panel.single()).with_children(|bar| {
+            for index in slot_count..hotbar.blocks.len() {
+                let icon = hotbar.icons.get(index).cloned().unwrap_or_default();
+                spawn_slot(bar, index, icon);
+            }
+        });
+    }
+    for (slot, mut border) in slots.iter_mut() {
+        border.0 = if slot.0 == hotbar.selected {
+            SELECTED_BORDER
+        } else {
+            SLOT_BORDER
+        };
+    }
+    for (icon, mut image) in icons.iter_mut() {
+        image.image = hotbar.icons.get(icon.0).cloned().unwrap_or_default();
+    }
+
+    let (mut cursor_cube, material) = cursor_cube.single_mut();
+    cursor_cube.voxel_mat = hotbar.selected_block();
+    if let Some(material) = materials.get_mut(&material.0) {
+        material.base_color_texture = hotbar.icons.get(hotbar.selected).cloned();
+        material.base_color = if material.base_color_texture.is_some() {
+            Color::srgba(1.0, 1.0, 1.0, 0.7)
+        } else {
+            Color::srgba_u8(124, 144, 255, 128)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_resource::TextureFormat;
+
+    #[test]
+    fn scroll_wraps_around() {
+        let mut sut = Hotbar::default();
+        sut.scroll(-1);
+        assert_eq!(sut.selected, HOTBAR_BLOCKS.len() - 1);
+        sut.scroll(2);
+        assert_eq!(sut.selected, 1);
+    }
+
+    #[test]
+    fn select_adds_missing_block() {
+        let mut sut = Hotbar::default();
+        sut.select(BlockTexture::FenceWoodPost);
+        assert_eq!(sut.selected_block(), BlockTexture::FenceWood);
+        sut.select(BlockTexture::EmeraldOre);
+        assert_eq!(sut.selected, HOTBAR_BLOCKS.len());
+        assert_eq!(sut.selected_block(), BlockTexture::EmeraldOre);
+    }
+
+    #[test]
+    fn layer_image_copies_one_layer() {
+        let data: Vec<u8> = (0..3u8).flat_map(|layer| [layer; 4 * 4]).collect();
+        let atlas = Image::new(
+            Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 3,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        let sut = layer_image(&atlas, 1).unwrap();
+        assert_eq!(sut.data, vec![1; 16]);
+        assert!(layer_image(&atlas, 3).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cursor::VoxelTrace;
+use crate::lighting::CHUNK_SIZE;
+use crate::map::MapNode;
+use crate::textures::BlockTexture;
+use crate::MyMainWorld;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+use std::fmt::Write;
+
+#[derive(Component)]
+pub(crate) struct HudText;
+
+pub(crate) fn setup_hud(mut commands: Commands) {
+    commands.spawn((
+        HudText,
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+    ));
+}
+
+fn chunk_of(pos: IVec3) -> IVec3 {
+    pos.div_euclid(IVec3::splat(CHUNK_SIZE))
+}
+
+/// The HUD lines for the voxel the cursor is on and the camera.
+fn hud_text(
+    target: Option<(IVec3, WorldVoxel<BlockTexture>, Option<MapNode>)>,
+    camera: Vec3,
+) -> String {
+    let mut text = String::new();
+    match target {
+        Some((pos, voxel, node)) => {
+            let block = match voxel {
+                WorldVoxel::Solid(block) => format!("{:?}", block),
+                WorldVoxel::Air => "Air".into(),
+                WorldVoxel::Unset => "Unset".into(),
+            };
+            let _ = writeln!(text, "Voxel {} {} {}  {}", pos.x, pos.y, pos.z, block);
+            match node {
+                Some(node) => {
+                    let _ = writeln!(
+                        text,
+                        "Column {:?} height {} moisture {}",
+                        node.surface_type, node.height, node.moisture
+                    );
+                }
+                None => text.push_str("Column outside map\n"),
+            }
+        }
+        None => text.push_str("Voxel -\n"),
+    }
+    let camera_voxel = camera.floor().as_ivec3();
+    let chunk = chunk_of(camera_voxel);
+    let _ = writeln!(
+        text,
+        "Camera {:.1} {:.1} {:.1}  chunk {} {} {}",
+        camera.x, camera.y, camera.z, chunk.x, chunk.y, chunk.z
+    );
+    text
+}
+
+pub(crate) fn update_hud(
+    voxel_world: VoxelWorld<MyMainWorld>,
+    world: Res<MyMainWorld>,
+    trace: Res<VoxelTrace>,
+    camera: Query<&Transform, With<VoxelWorldCamera<MyMainWorld>>>,
+    mut hud: Query<&mut Text, With<HudText>>,
+) {
+    let target = trace
+        .hit
+        .map(|pos| (pos, voxel_world.get_voxel(pos), world.static_map.get(pos)));
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    let text = hud_text(target, camera.translation);
+    let mut hud = hud.single_mut();
+    if hud.0 != text {
+        hud.0 = text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::NodeType;
+
+    #[test]
+    fn hud_shows_target_and_camera_chunk() {
+        let node = MapNode {
+            surface_type: NodeType::Sand,
+            height: 3,
+            moisture: 20,
+        };
+        let sut = hud_text(
+            Some((
+                IVec3::new(1, 3, -2),
+                WorldVoxel::Solid(BlockTexture::SandBrick),
+                Some(node),
+            )),
+            Vec3::new(40.0, 10.5, -0.5),
+        );
+        assert_eq!(
+            sut,
+            "Voxel 1 3 -2  SandBrick\nColumn Sand height 3 moisture 20\nCamera 40.0 10.5 -0.5  chunk 1 0 -1\n"
+        );
+    }
+}
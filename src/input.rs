@@ -0,0 +1,689 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The user's bindings, replacing the default ones of the actions listed in it.
+pub(crate) const BINDINGS_FILE: &str = "assets/input.bindings";
+const DEFAULT_BINDINGS: &str = include_str!("../assets/default.bindings");
+
+/// Something the user can do, bound to buttons in the bindings file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Action {
+    Quit,
+    OpenBindings,
+    ToggleConsole,
+    Place,
+    Remove,
+    Pick,
+    Select,
+    Undo,
+    Redo,
+    Fill,
+    Hollow,
+    ClearSelection,
+    DropSelection,
+    Copy,
+    Cut,
+    Paste,
+    SavePrefab,
+    LoadPrefab,
+    RotatePaste,
+    MirrorX,
+    MirrorZ,
+    CycleShape,
+    ToggleHollow,
+    RadiusDown,
+    RadiusUp,
+    HeightDown,
+    HeightUp,
+    Slot1,
+    Slot2,
+    Slot3,
+    Slot4,
+    Slot5,
+    Slot6,
+    Slot7,
+    Slot8,
+    Slot9,
+    Slot10,
+    NextBlock,
+    PreviousBlock,
+    SwitchCamera,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Look,
+    Drag,
+    Pan,
+    Orbit,
+    Zoom,
+    Bookmark,
+    NextBookmark,
+    RecordPath,
+    AddKeyframe,
+    PlayPath,
+    ToggleOverlay,
+    NextTexturePack,
+    ReloadTexturePack,
+}
+
+impl Action {
+    pub(crate) const ALL: [Action; 60] = [
+        Action::Quit,
+        Action::OpenBindings,
+        Action::ToggleConsole,
+        Action::Place,
+        Action::Remove,
+        Action::Pick,
+        Action::Select,
+        Action::Undo,
+        Action::Redo,
+        Action::Fill,
+        Action::Hollow,
+        Action::ClearSelection,
+        Action::DropSelection,
+        Action::Copy,
+        Action::Cut,
+        Action::Paste,
+        Action::SavePrefab,
+        Action::LoadPrefab,
+        Action::RotatePaste,
+        Action::MirrorX,
+        Action::MirrorZ,
+        Action::CycleShape,
+        Action::ToggleHollow,
+        Action::RadiusDown,
+        Action::RadiusUp,
+        Action::HeightDown,
+        Action::HeightUp,
+        Action::Slot1,
+        Action::Slot2,
+        Action::Slot3,
+        Action::Slot4,
+        Action::Slot5,
+        Action::Slot6,
+        Action::Slot7,
+        Action::Slot8,
+        Action::Slot9,
+        Action::Slot10,
+        Action::NextBlock,
+        Action::PreviousBlock,
+        Action::SwitchCamera,
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Jump,
+        Action::Look,
+        Action::Drag,
+        Action::Pan,
+        Action::Orbit,
+        Action::Zoom,
+        Action::Bookmark,
+        Action::NextBookmark,
+        Action::RecordPath,
+        Action::AddKeyframe,
+        Action::PlayPath,
+        Action::ToggleOverlay,
+        Action::NextTexturePack,
+        Action::ReloadTexturePack,
+    ];
+
+    /// The actions selecting the hotbar slots, in order.
+    pub(crate) const SLOTS: [Action; 10] = [
+        Action::Slot1,
+        Action::Slot2,
+        Action::Slot3,
+        Action::Slot4,
+        Action::Slot5,
+        Action::Slot6,
+        Action::Slot7,
+        Action::Slot8,
+        Action::Slot9,
+        Action::Slot10,
+    ];
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .into_iter()
+            .find(|action| format!("{:?}", action) == name)
+            .ok_or_else(|| format!("unknown action '{}'", name))
+    }
+}
+
+/// Keys that can be bound, modifiers are held together with them instead.
+pub(crate) const KEYS: [KeyCode; 87] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Escape,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::Backquote,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadSubtract,
+    KeyCode::NumpadEnter,
+];
+
+pub(crate) const MOUSE_BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Right,
+    MouseButton::Middle,
+    MouseButton::Back,
+    MouseButton::Forward,
+];
+
+pub(crate) const GAMEPAD_BUTTONS: [GamepadButton; 19] = [
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::North,
+    GamepadButton::West,
+    GamepadButton::C,
+    GamepadButton::Z,
+    GamepadButton::LeftTrigger,
+    GamepadButton::LeftTrigger2,
+    GamepadButton::RightTrigger,
+    GamepadButton::RightTrigger2,
+    GamepadButton::Select,
+    GamepadButton::Start,
+    GamepadButton::Mode,
+    GamepadButton::LeftThumb,
+    GamepadButton::RightThumb,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Button {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Button {
+    fn all() -> impl Iterator<Item = Button> {
+        KEYS.into_iter()
+            .map(Button::Key)
+            .chain(MOUSE_BUTTONS.into_iter().map(Button::Mouse))
+            .chain(GAMEPAD_BUTTONS.into_iter().map(Button::Gamepad))
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Button::Key(key) => write!(f, "{:?}", key),
+            Button::Mouse(button) => write!(f, "Mouse{:?}", button),
+            Button::Gamepad(button) => write!(f, "Gamepad{:?}", button),
+        }
+    }
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Button::all()
+            .find(|button| button.to_string() == name)
+            .ok_or_else(|| format!("unknown button '{}'", name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Modifiers {
+    pub(crate) ctrl: bool,
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+}
+
+impl Modifiers {
+    pub(crate) fn held(keys: &ButtonInput<KeyCode>) -> Self {
+        Self {
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+        }
+    }
+
+    fn contains(&self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+
+    fn count(&self) -> usize {
+        [self.ctrl, self.shift, self.alt]
+            .into_iter()
+            .filter(|held| *held)
+            .count()
+    }
+}
+
+/// A button, pressed while holding the modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Binding {
+    pub(crate) modifiers: Modifiers,
+    pub(crate) button: Button,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.modifiers.ctrl, "Ctrl+"),
+            (self.modifiers.shift, "Shift+"),
+            (self.modifiers.alt, "Alt+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        write!(f, "{}", self.button)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(binding: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = binding.split('+').collect();
+        let button = parts.pop().unwrap_or_default().parse()?;
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            let held = match part {
+                "Ctrl" => &mut modifiers.ctrl,
+                "Shift" => &mut modifiers.shift,
+                "Alt" => &mut modifiers.alt,
+                _ => return Err(format!("unknown modifier '{}'", part)),
+            };
+            *held = true;
+        }
+        Ok(Self { modifiers, button })
+    }
+}
+
+/// The bindings of every action.
+///
+/// The bindings file has a line per action with its bindings after it:
+///
+/// ```text
+/// # action bindings...
+/// Redo Ctrl+KeyY Ctrl+Shift+KeyZ
+/// ```
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub(crate) struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::parse("default bindings", DEFAULT_BINDINGS).expect("valid default bindings")
+    }
+}
+
+impl InputMap {
+    pub(crate) fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut bindings = HashMap::new();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: String| format!("{}:{}: {}", name, line_no + 1, msg);
+            let mut words = line.split_whitespace();
+            let action: Action = words.next().unwrap_or_default().parse().map_err(error)?;
+            let action_bindings = words
+                .map(|word| word.parse())
+                .collect::<Result<Vec<Binding>, String>>()
+                .map_err(error)?;
+            bindings.insert(action, action_bindings);
+        }
+        Ok(Self { bindings })
+    }
+
+    /// The bindings in `file` over the default ones, the defaults if it doesn't exist.
+    pub(crate) fn load(file: impl AsRef<Path>) -> Result<Self, String> {
+        let file = file.as_ref();
+        let mut map = Self::default();
+        match fs::read_to_string(file) {
+            Ok(source) => {
+                let loaded = Self::parse(&file.display().to_string(), &source)?;
+                map.bindings.extend(loaded.bindings);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("can't read {}: {}", file.display(), err)),
+        }
+        Ok(map)
+    }
+
+    pub(crate) fn save(&self, file: impl AsRef<Path>) -> Result<(), String> {
+        let file = file.as_ref();
+        fs::write(file, self.to_string())
+            .map_err(|err| format!("can't write {}: {}", file.display(), err))
+    }
+
+    pub(crate) fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn bindings_mut(&mut self, action: Action) -> &mut Vec<Binding> {
+        self.bindings.entry(action).or_default()
+    }
+
+    /// The actions with a binding whose button is down and whose modifiers are held. A binding
+    /// holding more modifiers on the same button takes over, so Ctrl+Z doesn't also trigger Z.
+    fn triggered(&self, held: Modifiers, down: impl Fn(Button) -> bool) -> HashSet<Action> {
+        let all: Vec<&Binding> = self.bindings.values().flatten().collect();
+        let mut actions = HashSet::new();
+        for (action, bindings) in &self.bindings {
+            let active = bindings.iter().any(|binding| {
+                down(binding.button)
+                    && held.contains(binding.modifiers)
+                    && !all.iter().any(|other| {
+                        other.button == binding.button
+                            && held.contains(other.modifiers)
+                            && other.modifiers.count() > binding.modifiers.count()
+                    })
+            });
+            if active {
+                actions.insert(*action);
+            }
+        }
+        actions
+    }
+
+    /// The buttons down for a binding that takes over bindings with fewer modifiers on them.
+    fn taken_over(&self, held: Modifiers, down: impl Fn(Button) -> bool) -> HashSet<Button> {
+        let all: Vec<&Binding> = self.bindings.values().flatten().collect();
+        all.iter()
+            .filter(|binding| {
+                down(binding.button)
+                    && held.contains(binding.modifiers)
+                    && all.iter().any(|other| {
+                        other.button == binding.button
+                            && other.modifiers.count() < binding.modifiers.count()
+                    })
+            })
+            .map(|binding| binding.button)
+            .collect()
+    }
+}
+
+impl fmt::Display for InputMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# action bindings...")?;
+        for action in Action::ALL {
+            write!(f, "{:?}", action)?;
+            for binding in self.bindings(action) {
+                write!(f, " {}", binding)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The actions triggered this frame.
+#[derive(Resource, Default)]
+pub(crate) struct Actions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    /// Buttons pressed for a binding with more modifiers. Their other actions stay off until
+    /// they are let go, even when the modifiers are let go first.
+    taken_over: HashSet<Button>,
+    /// No actions trigger while set, for screens reading the buttons themselves.
+    pub(crate) suspended: bool,
+}
+
+impl Actions {
+    pub(crate) fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub(crate) fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Actions with the given ones held since an earlier frame.
+    #[cfg(test)]
+    pub(crate) fn pressing(actions: impl IntoIterator<Item = Action>) -> Self {
+        Self {
+            pressed: actions.into_iter().collect(),
+            ..default()
+        }
+    }
+
+    /// Stop the action from triggering any other system this frame.
+    pub(crate) fn consume(&mut self, action: Action) {
+        self.just_pressed.remove(&action);
+    }
+
+    fn update(
+        &mut self,
+        map: &InputMap,
+        held: Modifiers,
+        pressed: impl Fn(Button) -> bool,
+        just_pressed: impl Fn(Button) -> bool,
+    ) {
+        self.taken_over.retain(|button| pressed(*button));
+        let free = |button| !self.taken_over.contains(&button);
+        self.just_pressed = map.triggered(held, |button| just_pressed(button) && free(button));
+        // Actions stay pressed while their button is, even when the modifiers are let go
+        let still_pressed: Vec<Action> = self
+            .pressed
+            .iter()
+            .copied()
+            .filter(|action| map.bindings(*action).iter().any(|b| pressed(b.button)))
+            .collect();
+        self.pressed = map.triggered(held, |button| pressed(button) && free(button));
+        self.pressed.extend(still_pressed);
+        self.taken_over.extend(map.taken_over(held, &just_pressed));
+    }
+}
+
+/// Update the actions every frame from the bindings inserted before, or the default ones. Every
+/// plugin that reads actions calls it, only the first call adds them.
+pub(crate) fn add_actions(app: &mut App) {
+    if app.world().contains_resource::<Actions>() {
+        return;
+    }
+    app.init_resource::<InputMap>()
+        .init_resource::<Actions>()
+        .add_systems(PreUpdate, update_actions.after(InputSystem));
+}
+
+pub(crate) fn update_actions(
+    map: Res<InputMap>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut actions: ResMut<Actions>,
+) {
+    if actions.suspended {
+        actions.pressed.clear();
+        actions.just_pressed.clear();
+        return;
+    }
+    actions.update(
+        &map,
+        Modifiers::held(&keys),
+        |button| match button {
+            Button::Key(key) => keys.pressed(key),
+            Button::Mouse(button) => mouse.pressed(button),
+            Button::Gamepad(button) => gamepads.iter().any(|pad| pad.pressed(button)),
+        },
+        |button| match button {
+            Button::Key(key) => keys.just_pressed(key),
+            Button::Mouse(button) => mouse.just_pressed(button),
+            Button::Gamepad(button) => gamepads.iter().any(|pad| pad.just_pressed(button)),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_cover_all_actions() {
+        let sut = InputMap::default();
+        for action in Action::ALL {
+            assert!(!sut.bindings(action).is_empty(), "{:?} is unbound", action);
+        }
+        assert_eq!(InputMap::parse("saved", &sut.to_string()), Ok(sut));
+    }
+
+    #[test]
+    fn parse_reports_line() {
+        let err = InputMap::parse("test", "Undo Ctrl+KeyZ\nRedo Hyper+KeyY").unwrap_err();
+        assert_eq!(err, "test:2: unknown modifier 'Hyper'");
+        let binding: Binding = "Shift+MouseLeft".parse().unwrap();
+        assert_eq!(binding.button, Button::Mouse(MouseButton::Left));
+        assert_eq!(binding.to_string(), "Shift+MouseLeft");
+    }
+
+    #[test]
+    fn more_modifiers_take_over() {
+        let map = InputMap::default();
+        let z = |button| button == Button::Key(KeyCode::KeyZ);
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..default()
+        };
+        let ctrl_shift = Modifiers {
+            shift: true,
+            ..ctrl
+        };
+        assert!(map.triggered(ctrl, z).contains(&Action::Undo));
+        let redo = map.triggered(ctrl_shift, z);
+        assert!(redo.contains(&Action::Redo) && !redo.contains(&Action::Undo));
+        // Unrelated modifiers don't stop an action
+        let w = |button| button == Button::Key(KeyCode::KeyW);
+        assert!(map.triggered(ctrl_shift, w).contains(&Action::MoveForward));
+    }
+
+    #[test]
+    fn pressed_outlasts_modifier() {
+        let map = InputMap::default();
+        let left = |button| button == Button::Mouse(MouseButton::Left);
+        let shift = Modifiers {
+            shift: true,
+            ..default()
+        };
+        let mut sut = Actions::default();
+        sut.update(&map, shift, left, left);
+        assert!(sut.just_pressed(Action::Select) && !sut.just_pressed(Action::Place));
+        sut.update(&map, Modifiers::default(), left, |_| false);
+        assert!(sut.pressed(Action::Select));
+    }
+
+    #[test]
+    fn released_modifier_keeps_button_taken_over() {
+        let map = InputMap::default();
+        let left = |button| button == Button::Mouse(MouseButton::Left);
+        let shift = Modifiers {
+            shift: true,
+            ..default()
+        };
+        let mut sut = Actions::default();
+        sut.update(&map, shift, left, left);
+        sut.update(&map, shift, left, |_| false);
+        sut.update(&map, Modifiers::default(), left, |_| false);
+        assert!(!sut.pressed(Action::Drag) && !sut.pressed(Action::Place));
+        // Letting go of the button frees it again
+        sut.update(&map, Modifiers::default(), |_| false, |_| false);
+        sut.update(&map, Modifiers::default(), left, left);
+        assert!(sut.just_pressed(Action::Place) && sut.pressed(Action::Drag));
+        assert!(!sut.pressed(Action::Select));
+    }
+}
@@ -0,0 +1,215 @@
+//! A voxel world generated from a map of terrain columns, with water, editing tools and a
+//! console. The binaries are built on this library: the demo, the terrain editor and the
+//! headless map tool.
+//!
+//! The public API is the map with its file formats, the block types and the configs of the two
+//! voxel worlds, and the plugins the demo is composed of: the terrain, the water, the cursor,
+//! the edit tools and the camera. [`demo_app`] builds the whole demo.
+
+mod blocks;
+mod bookmarks;
+mod camera;
+mod chunk_mesh;
+mod clipboard;
+mod commands;
+mod connected;
+mod console;
+mod cursor;
+mod debug_overlay;
+mod editing;
+pub mod heightmap;
+mod history;
+mod hotbar;
+mod hud;
+mod input;
+mod lighting;
+pub mod map;
+pub mod map_file;
+mod minimap;
+pub mod options;
+mod player;
+mod rebinding;
+mod scripting;
+mod selection;
+mod shapes;
+mod terrain;
+mod texture_pack;
+pub mod textures;
+mod tools;
+pub mod vox;
+mod water;
+
+pub use map::{GenParams, Map, MapNode, NodeType, Size};
+pub use map_file::MapFile;
+pub use textures::BlockTexture;
+
+pub use camera::{DemoCameraPlugin, DemoCameraSet, DemoCameraSettings};
+pub use cursor::{CursorEditPlugin, CursorEditSettings, CursorSet};
+pub use terrain::{MyMainWorld, TerrainPlugin, TerrainSettings};
+pub use tools::EditToolsPlugin;
+pub use water::{WaterPlugin, WaterSettings, WaterWorld};
+
+use crate::console::Console;
+use crate::debug_overlay::{ChunkStats, DebugOverlay};
+use crate::history::EditHistory;
+use crate::input::{Action, Actions, InputMap};
+use crate::options::Options;
+use crate::rebinding::Rebinding;
+use bevy::prelude::*;
+use bevy::window::PresentMode;
+use bevy_voxel_world::prelude::*;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// How the map is generated, `regen` makes a new one from it.
+#[derive(Resource, Debug, Clone, Copy)]
+struct WorldGen {
+    size: Size,
+    /// Parameters of the next map generated.
+    params: GenParams,
+    /// Seed the current map was generated from, none for a loaded map that wasn't.
+    map_seed: Option<u32>,
+}
+
+/// The demo with the map, window and camera of the options, fails when the map file given
+/// can't be loaded.
+pub fn demo_app(options: Options) -> Result<App, String> {
+    assert_eq!(size_of::<WorldVoxel>(), 2);
+    assert_eq!(size_of::<WorldVoxel<BlockTexture>>(), 1);
+
+    let startup_map = options.map.as_ref().map(MapFile::load).transpose()?;
+
+    let world_gen = WorldGen {
+        size: options.size,
+        params: options.params,
+        map_seed: startup_map
+            .as_ref()
+            .map_or(Some(options.params.seed), |file| file.seed),
+    };
+    let map = match &startup_map {
+        Some(file) => file.map.clone(),
+        None => Map::generate(world_gen.size, &world_gen.params),
+    };
+    let static_map = Arc::new(map);
+    let camera = DemoCameraSettings {
+        position: options.camera,
+        look_at: options.look_at,
+        // Camera bookmarks are kept next to the map
+        bookmarks: options
+            .map
+            .as_ref()
+            .map_or(PathBuf::from(bookmarks::DEFAULT_CAMERA_FILE), |file| {
+                file.with_extension("cameras")
+            }),
+    };
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            resolution: options.window.as_vec2().into(),
+            present_mode: if options.vsync {
+                PresentMode::AutoVsync
+            } else {
+                PresentMode::AutoNoVsync
+            },
+            ..default()
+        }),
+        ..default()
+    }));
+    // Loaded once the log plugin is added, so the warning isn't lost
+    let input_map = InputMap::load(input::BINDINGS_FILE).unwrap_or_else(|err| {
+        warn!("{}", err);
+        InputMap::default()
+    });
+    app.insert_resource(input_map);
+    input::add_actions(&mut app);
+    editing::add_editing(&mut app);
+    app.add_plugins((
+        TerrainPlugin::new(static_map.clone()),
+        WaterPlugin::new(static_map),
+        CursorEditPlugin::default(),
+        EditToolsPlugin,
+        DemoCameraPlugin { settings: camera },
+    ))
+    .init_resource::<Rebinding>()
+    .insert_resource(world_gen)
+    .insert_resource(commands::builtin())
+    .init_resource::<Console>()
+    .init_resource::<DebugOverlay>()
+    .init_resource::<ChunkStats<MyMainWorld>>()
+    .init_resource::<ChunkStats<WaterWorld>>()
+    .insert_resource(EditHistory::new(options.history))
+    // The camera moves between finding the voxel at the cursor and editing it
+    .configure_sets(
+        Update,
+        DemoCameraSet
+            .after(CursorSet::Trace)
+            .before(CursorSet::Tools),
+    )
+    // After the plugins have spawned the camera, the cursor and the sun, and filled the water
+    .add_systems(
+        PostStartup,
+        (
+            commands::restore_startup_edits,
+            hud::setup_hud,
+            debug_overlay::setup_debug_overlay,
+            minimap::setup_minimap,
+            rebinding::setup_rebinding,
+            console::setup_console,
+            scripting::run_startup_script,
+        )
+            .chain(),
+    )
+    .add_systems(
+        Update,
+        (
+            (
+                close_on_esc,
+                rebinding::rebind_actions,
+                console::console_input,
+                console::run_console_commands,
+                console::draw_console,
+            )
+                .chain(),
+            minimap::click_minimap
+                .after(CursorSet::Trace)
+                .before(selection::drag_selection),
+            (history::undo_redo, history::record_edits)
+                .chain()
+                .after(CursorSet::Edit)
+                .before(lighting::update_block_light),
+            hud::update_hud.after(CursorSet::Trace),
+            (minimap::update_minimap_terrain, minimap::draw_minimap)
+                .chain()
+                .after(DemoCameraSet),
+            (
+                debug_overlay::track_chunks::<MyMainWorld>,
+                debug_overlay::track_chunks::<WaterWorld>,
+                debug_overlay::toggle_debug_overlay,
+                debug_overlay::update_debug_overlay,
+                debug_overlay::draw_chunk_boundaries::<MyMainWorld>(Color::srgb(0.2, 1.0, 0.3)),
+                debug_overlay::draw_chunk_boundaries::<WaterWorld>(Color::srgb(0.2, 0.5, 1.0)),
+            )
+                .chain(),
+        ),
+    );
+    if let Some(file) = startup_map {
+        app.insert_resource(commands::StartupMap(file));
+    }
+    Ok(app)
+}
+
+fn close_on_esc(
+    mut commands: Commands,
+    focused_windows: Query<(Entity, &Window)>,
+    actions: Res<Actions>,
+) {
+    for (window, focus) in focused_windows.iter() {
+        if !focus.focused {
+            continue;
+        }
+
+        if actions.just_pressed(Action::Quit) {
+            commands.entity(window).despawn();
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::blocks::BlockProperties;
+use crate::editing::VoxelChanged;
+use crate::terrain::terrain_voxel;
+use crate::textures::BlockTexture;
+use crate::MyMainWorld;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_voxel_world::prelude::*;
+use std::collections::VecDeque;
+
+/// Same as the chunk size of bevy_voxel_world, which doesn't export it.
+pub(crate) const CHUNK_SIZE: i32 = 32;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+fn passes_light(voxel: WorldVoxel<BlockTexture>) -> bool {
+    let properties = BlockProperties::of(voxel);
+    !properties.collidable || properties.transparent
+}
+
+fn chunk_and_index(pos: IVec3) -> (IVec3, usize) {
+    let chunk = pos.div_euclid(IVec3::splat(CHUNK_SIZE));
+    let local = pos.rem_euclid(IVec3::splat(CHUNK_SIZE));
+    let index = (local.x * CHUNK_SIZE + local.y) * CHUNK_SIZE + local.z;
+    (chunk, index as usize)
+}
+
+/// Block light levels of the terrain world, flood filled from emissive blocks.
+/// Levels are stored per chunk, only for chunks that have any light.
+#[derive(Resource, Default)]
+pub(crate) struct BlockLight {
+    chunks: HashMap<IVec3, Box<[u8; CHUNK_VOLUME]>>,
+    /// Chunks with meshes that need the new light levels baked in.
+    dirty_chunks: HashSet<IVec3>,
+}
+
+impl BlockLight {
+    pub(crate) fn get(&self, pos: IVec3) -> u8 {
+        let (chunk, index) = chunk_and_index(pos);
+        self.chunks.get(&chunk).map_or(0, |levels| levels[index])
+    }
+
+    fn set(&mut self, pos: IVec3, level: u8) {
+        let (chunk, index) = chunk_and_index(pos);
+        let levels = match self.chunks.get_mut(&chunk) {
+            Some(levels) => levels,
+            None if level == 0 => return,
+            None => self
+                .chunks
+                .entry(chunk)
+                .or_insert_with(|| Box::new([0; CHUNK_VOLUME])),
+        };
+        if levels[index] == level {
+            return;
+        }
+        levels[index] = level;
+
+        // Faces of the neighbouring voxels are lit by this voxel, they can be in another chunk
+        for offset in NEIGHBOURS.iter().chain([&IVec3::ZERO]) {
+            self.dirty_chunks.insert(chunk_and_index(pos + *offset).0);
+        }
+    }
+
+    /// Chunks that changed light since the last call.
+    pub(crate) fn take_dirty_chunks(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+
+    /// Update the light around `pos` after the voxel there has changed.
+    pub(crate) fn voxel_changed(
+        &mut self,
+        get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+        pos: IVec3,
+    ) {
+        let voxel = get_voxel(pos);
+        let mut refill = self.remove_light(get_voxel, pos);
+
+        let emissive = BlockProperties::of(voxel).emissive;
+        if emissive > 0 {
+            self.set(pos, emissive);
+            refill.push_back(pos);
+        } else if passes_light(voxel) {
+            // Light from around flows into the opened up space
+            refill.extend(NEIGHBOURS.iter().map(|offset| pos + *offset));
+        }
+        self.spread(get_voxel, refill);
+    }
+
+    /// Darken the voxel at `pos` and all light that spread from it. Returns the voxels at the
+    /// edge of the darkened area, that still have light to spread back into it.
+    fn remove_light(
+        &mut self,
+        get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+        pos: IVec3,
+    ) -> VecDeque<IVec3> {
+        let mut refill = VecDeque::new();
+        let level = self.get(pos);
+        if level == 0 {
+            return refill;
+        }
+        self.set(pos, 0);
+
+        let mut queue = VecDeque::from([(pos, level)]);
+        while let Some((pos, level)) = queue.pop_front() {
+            for neighbour in NEIGHBOURS.iter().map(|offset| pos + *offset) {
+                let neighbour_level = self.get(neighbour);
+                if neighbour_level == 0 {
+                    continue;
+                }
+                if neighbour_level < level {
+                    self.set(neighbour, 0);
+                    queue.push_back((neighbour, neighbour_level));
+                    // Weaker lights inside the darkened area still shine
+                    let emissive = BlockProperties::of(get_voxel(neighbour)).emissive;
+                    if emissive > 0 {
+                        self.set(neighbour, emissive);
+                        refill.push_back(neighbour);
+                    }
+                } else {
+                    refill.push_back(neighbour);
+                }
+            }
+        }
+        refill
+    }
+
+    fn spread(
+        &mut self,
+        get_voxel: &impl Fn(IVec3) -> WorldVoxel<BlockTexture>,
+        mut queue: VecDeque<IVec3>,
+    ) {
+        while let Some(pos) = queue.pop_front() {
+            let level = self.get(pos);
+            if level <= 1 {
+                continue;
+            }
+            for neighbour in NEIGHBOURS.iter().map(|offset| pos + *offset) {
+                if self.get(neighbour) + 1 < level && passes_light(get_voxel(neighbour)) {
+                    self.set(neighbour, level - 1);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+/// Relight the terrain around every edited voxel.
+pub(crate) fn update_block_light(
+    mut light: ResMut<BlockLight>,
+    mut changes: EventReader<VoxelChanged>,
+    voxel_world: VoxelWorld<MyMainWorld>,
+    world: Res<MyMainWorld>,
+) {
+    if changes.is_empty() {
+        return;
+    }
+    let get_voxel = voxel_world.get_voxel_fn();
+    let map = &world.static_map;
+    // Light can travel into chunks that haven't spawned yet, use the map for those
+    let lookup = |pos| match get_voxel(pos) {
+        WorldVoxel::Unset => terrain_voxel(map, pos),
+        voxel => voxel,
+    };
+    for change in changes.read() {
+        light.voxel_changed(&lookup, change.pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::MAX_LIGHT;
+
+    fn world(blocks: Vec<(IVec3, BlockTexture)>) -> impl Fn(IVec3) -> WorldVoxel<BlockTexture> {
+        let blocks: HashMap<IVec3, BlockTexture> = blocks.into_iter().collect();
+        move |pos| {
+            blocks
+                .get(&pos)
+                .map(|block| WorldVoxel::Solid(*block))
+                .unwrap_or(WorldVoxel::Air)
+        }
+    }
+
+    #[test]
+    fn light_falls_off_with_distance() {
+        let get_voxel = world(vec![(IVec3::ZERO, BlockTexture::Lava)]);
+        let mut sut = BlockLight::default();
+        sut.voxel_changed(&get_voxel, IVec3::ZERO);
+        assert_eq!(sut.get(IVec3::ZERO), MAX_LIGHT);
+        assert_eq!(sut.get(IVec3::new(3, 0, 0)), MAX_LIGHT - 3);
+        assert_eq!(sut.get(IVec3::new(-2, 2, 1)), MAX_LIGHT - 5);
+        assert_eq!(sut.get(IVec3::new(0, 15, 0)), 0);
+    }
+
+    #[test]
+    fn solid_blocks_stop_light() {
+        let mut get_voxel = world(vec![(IVec3::ZERO, BlockTexture::Lava)]);
+        let mut sut = BlockLight::default();
+        sut.voxel_changed(&get_voxel, IVec3::ZERO);
+
+        get_voxel = world(vec![
+            (IVec3::ZERO, BlockTexture::Lava),
+            (IVec3::X, BlockTexture::StoneBrick),
+        ]);
+        sut.voxel_changed(&get_voxel, IVec3::X);
+        assert_eq!(sut.get(IVec3::X), 0);
+        // Light now has to go around the stone
+        assert_eq!(sut.get(IVec3::new(2, 0, 0)), MAX_LIGHT - 4);
+    }
+
+    #[test]
+    fn removing_light_keeps_other_lights() {
+        let mut get_voxel = world(vec![
+            (IVec3::ZERO, BlockTexture::Lava),
+            (IVec3::new(4, 0, 0), BlockTexture::DiamondOre),
+        ]);
+        let mut sut = BlockLight::default();
+        sut.voxel_changed(&get_voxel, IVec3::ZERO);
+        sut.voxel_changed(&get_voxel, IVec3::new(4, 0, 0));
+
+        get_voxel = world(vec![(IVec3::new(4, 0, 0), BlockTexture::DiamondOre)]);
+        sut.voxel_changed(&get_voxel, IVec3::ZERO);
+        let diamond = BlockTexture::DiamondOre.properties().emissive;
+        assert_eq!(sut.get(IVec3::new(4, 0, 0)), diamond);
+        assert_eq!(sut.get(IVec3::ZERO), diamond - 4);
+        assert_eq!(sut.get(IVec3::new(-20, 0, 0)), 0);
+    }
+
+    #[test]
+    fn dirty_chunks_include_neighbours_at_edge() {
+        let get_voxel = world(vec![(IVec3::new(31, 0, 0), BlockTexture::RubyOre)]);
+        let mut sut = BlockLight::default();
+        sut.voxel_changed(&get_voxel, IVec3::new(31, 0, 0));
+        let dirty = sut.take_dirty_chunks();
+        assert!(dirty.contains(&IVec3::ZERO));
+        assert!(dirty.contains(&IVec3::X));
+        assert!(dirty.contains(&IVec3::NEG_Y));
+        assert!(sut.take_dirty_chunks().is_empty());
+    }
+}
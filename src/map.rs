@@ -1,22 +1,54 @@
-use bevy::math::IVec3;
+use bevy::math::{IVec2, IVec3, UVec2, Vec2};
+use bevy::render::render_resource::Extent3d;
 use bevy_voxel_world::prelude::WorldVoxel;
-use noise::{HybridMulti, NoiseFn, Perlin};
+use noise::{HybridMulti, MultiFractal, NoiseFn, Perlin};
 use crate::textures::BlockTexture;
+use std::str::FromStr;
 
+/// Number of columns of a map along x and z.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
-pub(crate) struct Size {
+pub struct Size {
     width: u32,
     height: u32,
 }
 
 impl Size {
-    pub(crate) fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32) -> Self {
         Size { width, height }
     }
 }
 
+/// Seed of the terrain noise when none is given.
+pub const DEFAULT_SEED: u32 = 1234;
+
+/// Parameters of the terrain noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenParams {
+    pub seed: u32,
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+    /// Scale of the noise to heights in voxels.
+    pub amplitude: f64,
+}
+
+impl Default for GenParams {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            octaves: 5,
+            frequency: 1.1,
+            lacunarity: 2.8,
+            persistence: 0.4,
+            amplitude: 50.0,
+        }
+    }
+}
+
+/// The terrain at the top of a column.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
-pub(crate) enum NodeType {
+pub enum NodeType {
     #[default]
     Grass,
     Snow,
@@ -28,18 +60,77 @@ pub(crate) enum NodeType {
     Water,
 }
 
+impl NodeType {
+    pub const ALL: [NodeType; 8] = [
+        NodeType::Grass,
+        NodeType::Snow,
+        NodeType::Dirt,
+        NodeType::Sand,
+        NodeType::Gravel,
+        NodeType::Stone,
+        NodeType::Rock,
+        NodeType::Water,
+    ];
+
+    /// The surface generated for a column of this height.
+    pub fn at_height(height: i8) -> NodeType {
+        match height {
+            x if x < 0 => NodeType::Gravel,
+            0 => NodeType::Sand,
+            x if x > 15 => NodeType::Stone,
+            x if x > 30 => NodeType::Rock,
+            x if x > 35 => NodeType::Snow,
+            _ => NodeType::Grass,
+        }
+    }
+
+    /// The block at the surface of a column of this type.
+    pub fn block(&self) -> BlockTexture {
+        match self {
+            NodeType::Grass => BlockTexture::GrassBrick,
+            NodeType::Snow => BlockTexture::SnowyBrick,
+            NodeType::Dirt => BlockTexture::DirtBrick,
+            NodeType::Sand => BlockTexture::SandBrick,
+            NodeType::Gravel => BlockTexture::GravelBrick,
+            NodeType::Stone => BlockTexture::StoneBrick,
+            NodeType::Rock => BlockTexture::RockBrick,
+            NodeType::Water => BlockTexture::WaterBrick,
+        }
+    }
+}
+
+impl FromStr for NodeType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        NodeType::ALL
+            .into_iter()
+            .find(|node_type| format!("{:?}", node_type) == name)
+            .ok_or_else(|| format!("unknown surface type '{}'", name))
+    }
+}
+
+/// A column of the map.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
-pub(crate) struct MapNode {
-    pub(crate) surface_type: NodeType,
-    pub(crate) height: i8,
+pub struct MapNode {
+    pub surface_type: NodeType,
+    pub height: i8,
+    /// How wet the column is, used to tint grass and leaves.
+    pub moisture: u8,
 }
 
 impl MapNode {
+    #[cfg(test)]
     fn new(surface_type: NodeType, height: i8) -> Self {
-        Self { surface_type, height }
+        Self {
+            surface_type,
+            height,
+            moisture: 0,
+        }
     }
 }
 
+/// Columns of terrain around the origin, solid up to their height with their surface on top.
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Default)]
 pub struct Map {
     pub(crate) size: Size,
@@ -51,12 +142,20 @@ pub struct Map {
 }
 
 impl Map {
-    pub(crate) fn noise_map(size: Size) -> Self {
-        let mut noise = HybridMulti::<Perlin>::new(1234);
-        noise.octaves = 5;
-        noise.frequency = 1.1;
-        noise.lacunarity = 2.8;
-        noise.persistence = 0.4;
+    pub fn noise_map(size: Size) -> Self {
+        Self::generate(size, &GenParams::default())
+    }
+
+    /// Terrain from noise, the same parameters always give the same map.
+    pub fn generate(size: Size, params: &GenParams) -> Self {
+        // Setting the octaves makes a noise source per octave
+        let noise = HybridMulti::<Perlin>::new(params.seed)
+            .set_octaves(params.octaves)
+            .set_frequency(params.frequency)
+            .set_lacunarity(params.lacunarity)
+            .set_persistence(params.persistence);
+        // 4321 for the default seed
+        let moisture_noise = Perlin::new(params.seed.wrapping_add(3087));
 
         let min_x = 0 - (size.width / 2) as i32;
         let max_x = min_x + size.width as i32;
@@ -66,20 +165,17 @@ impl Map {
         for x in min_x..max_x {
             let mut row = Vec::new();
             for z in min_z..max_z {
-                let float_height = noise.get([x as f64 / 1000.0, z as f64 / 1000.0]) * 50.0;
+                let float_height =
+                    noise.get([x as f64 / 1000.0, z as f64 / 1000.0]) * params.amplitude;
                 let height = float_height.floor() as i8;
                 // println!("new float height: {} {}", float_height, height);
-                let surface_type = match height {
-                    x if x < 0 => NodeType::Gravel,
-                    x if x == 0 => NodeType::Sand,
-                    x if x > 15 => NodeType::Stone,
-                    x if x > 30 => NodeType::Rock,
-                    x if x > 35 => NodeType::Snow,
-                    _ => NodeType::Grass,
-                };
+                let surface_type = NodeType::at_height(height);
+                let moisture =
+                    (moisture_noise.get([x as f64 / 150.0, z as f64 / 150.0]) + 1.0) * 127.5;
                 row.push(MapNode {
                     surface_type,
                     height,
+                    moisture: moisture.clamp(0.0, 255.0) as u8,
                 })
             }
             // println!();
@@ -97,6 +193,34 @@ impl Map {
         m.set_surface(IVec3::new(30,0, 67), NodeType::Water);
         m
     }
+    /// A map of the given columns, a row of columns along z for every x.
+    pub fn from_rows(map: Vec<Vec<MapNode>>) -> Result<Self, String> {
+        let depth = map.first().map_or(0, Vec::len);
+        if depth == 0 {
+            return Err("a map needs at least one column".into());
+        }
+        if let Some(row) = map.iter().position(|row| row.len() != depth) {
+            return Err(format!(
+                "row {} has {} columns, expected {}",
+                row + 1,
+                map[row].len(),
+                depth
+            ));
+        }
+        let size = Size::new(map.len() as u32, depth as u32);
+        let min_x = 0 - (size.width / 2) as i32;
+        let min_z = 0 - (size.height / 2) as i32;
+        Ok(Self {
+            size,
+            map,
+            min_x,
+            max_x: min_x + size.width as i32,
+            min_z,
+            max_z: min_z + size.height as i32,
+        })
+    }
+
+    #[cfg(test)]
     pub(crate) fn test_map() -> Self {
         let width = 11;
         let height = 10;
@@ -248,11 +372,43 @@ impl Map {
         }
     }
 
-    fn in_map(self: &Self, pos: IVec3) -> bool {
+    /// The column with the lowest x and z.
+    pub fn min_corner(&self) -> IVec2 {
+        IVec2::new(self.min_x, self.min_z)
+    }
+
+    /// Number of columns along x and z.
+    pub fn extent(&self) -> UVec2 {
+        UVec2::new(self.size.width, self.size.height)
+    }
+
+    /// The column under a position on a top down image of the map, from (0, 0) at the top left
+    /// to (1, 1).
+    pub fn column_at(&self, normalized: Vec2) -> IVec2 {
+        let extent = self.extent().as_ivec2();
+        let pixel = (normalized * extent.as_vec2()).floor().as_ivec2();
+        self.min_corner() + pixel.clamp(IVec2::ZERO, extent - 1)
+    }
+
+    /// Size of a top down image of the map, a pixel per column.
+    pub fn image_size(&self) -> Extent3d {
+        Extent3d {
+            width: self.size.width,
+            height: self.size.height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    /// The columns, a row of columns along z for every x from the lowest.
+    pub fn rows(&self) -> &[Vec<MapNode>] {
+        &self.map
+    }
+
+    fn in_map(&self, pos: IVec3) -> bool {
         pos.x >= self.min_x && pos.x < self.max_x && pos.z >= self.min_z && pos.z < self.max_z
     }
 
-    pub(crate) fn get(self: &Self, pos: IVec3) -> Option<MapNode> {
+    pub fn get(&self, pos: IVec3) -> Option<MapNode> {
         if self.in_map(pos) {
             let x = (pos.x + self.min_x.abs()) as usize;
             let z = (pos.z + self.min_z.abs()) as usize;
@@ -262,7 +418,7 @@ impl Map {
         }
     }
 
-    pub(crate) fn voxel_at(self: &Self, pos: IVec3) -> WorldVoxel<BlockTexture> {
+    pub fn voxel_at(&self, pos: IVec3) -> WorldVoxel<BlockTexture> {
         let node = self.get(pos);
         match node {
             None => WorldVoxel::Unset,
@@ -270,16 +426,7 @@ impl Map {
                 if pos.y < (n.height as i32) {
                     WorldVoxel::Solid(BlockTexture::FullBrick)
                 } else if pos.y == (n.height as i32) {
-                    match n.surface_type {
-                        NodeType::Grass => WorldVoxel::Solid(BlockTexture::GrassBrick),
-                        NodeType::Snow => WorldVoxel::Solid(BlockTexture::SnowyBrick),
-                        NodeType::Dirt => WorldVoxel::Solid(BlockTexture::DirtBrick),
-                        NodeType::Sand => WorldVoxel::Solid(BlockTexture::SandBrick),
-                        NodeType::Gravel => WorldVoxel::Solid(BlockTexture::GravelBrick),
-                        NodeType::Stone => WorldVoxel::Solid(BlockTexture::StoneBrick),
-                        NodeType::Rock => WorldVoxel::Solid(BlockTexture::RockBrick),
-                        NodeType::Water => WorldVoxel::Solid(BlockTexture::WaterBrick),
-                    }
+                    WorldVoxel::Solid(n.surface_type.block())
                 } else {
                     WorldVoxel::Air
                 }
@@ -287,7 +434,7 @@ impl Map {
         }
     }
 
-    fn set_surface(self: &mut Self, pos: IVec3, surface: NodeType) {
+    pub fn set_surface(&mut self, pos: IVec3, surface: NodeType) {
         if self.in_map(pos) {
             let x = (pos.x + self.min_x.abs()) as usize;
             let z = (pos.z + self.min_z.abs()) as usize;
@@ -297,13 +444,30 @@ impl Map {
 
 
     }
+
+    pub fn set_height(&mut self, pos: IVec3, height: i8) {
+        if self.in_map(pos) {
+            let x = (pos.x - self.min_x) as usize;
+            let z = (pos.z - self.min_z) as usize;
+            self.map[x][z].height = height;
+        }
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use bevy::prelude::IVec3;
 
+    #[test]
+    fn column_at_image_position() {
+        let sut = Map::test_map();
+        assert_eq!(sut.column_at(Vec2::new(0.0, 0.999)), IVec2::new(-5, 4));
+        assert_eq!(sut.column_at(Vec2::new(1.0, 0.0)), IVec2::new(5, -5));
+        assert_eq!((sut.image_size().width, sut.image_size().height), (11, 10));
+    }
+
     #[test]
     fn in_map_n5xn5() {
         let sut = Map::test_map();
@@ -371,4 +535,29 @@ mod tests {
         let position = sut.get(IVec3::new(0, 0, 0));
         assert_ne!(position, None);
     }
+
+    #[test]
+    fn seed_changes_terrain() {
+        let size = Size::new(20, 20);
+        assert_eq!(
+            Map::generate(size, &GenParams::default()),
+            Map::noise_map(size)
+        );
+        let params = GenParams {
+            seed: 7,
+            ..GenParams::default()
+        };
+        assert_ne!(Map::generate(size, &params), Map::noise_map(size));
+    }
+
+    #[test]
+    fn from_rows_matches_test_map() {
+        let sut = Map::test_map();
+        assert_eq!(Map::from_rows(sut.map.clone()), Ok(sut));
+        let ragged = vec![vec![MapNode::default(); 2], vec![MapNode::default()]];
+        assert_eq!(
+            Map::from_rows(ragged),
+            Err("row 2 has 1 columns, expected 2".into())
+        );
+    }
 }
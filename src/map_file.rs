@@ -0,0 +1,202 @@
+use crate::map::{Map, MapNode};
+use crate::textures::BlockTexture;
+use bevy::math::IVec3;
+use bevy_voxel_world::prelude::WorldVoxel;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Where maps saved by name go.
+pub const MAPS_DIR: &str = "assets/maps";
+pub const MAP_EXTENSION: &str = "map";
+
+/// A map with the edits made to it.
+///
+/// The file has a line per row of columns along z, one for every x, then a line per edited
+/// voxel:
+///
+/// ```text
+/// # seed the map was generated from, if it was
+/// seed 1234
+/// # row surface:height:moisture...
+/// row Grass:5:120 Sand:0:98
+/// # voxel x y z block, Air where a voxel was cleared
+/// voxel 3 6 -2 StoneBrick
+/// # water x y z material, Air where water was removed
+/// water 3 7 -2 2
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapFile {
+    pub map: Map,
+    pub seed: Option<u32>,
+    pub voxels: Vec<(IVec3, WorldVoxel<BlockTexture>)>,
+    pub water: Vec<(IVec3, WorldVoxel<u8>)>,
+}
+
+fn parse_node(word: &str) -> Result<MapNode, String> {
+    let error = || format!("invalid column '{}'", word);
+    let mut parts = word.split(':');
+    let (Some(surface), Some(height), Some(moisture), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(error());
+    };
+    Ok(MapNode {
+        surface_type: surface.parse()?,
+        height: height.parse().map_err(|_| error())?,
+        moisture: moisture.parse().map_err(|_| error())?,
+    })
+}
+
+fn parse_pos(words: &[&str]) -> Result<IVec3, String> {
+    let mut pos = IVec3::ZERO;
+    for (axis, word) in words.iter().enumerate() {
+        pos[axis] = word
+            .parse()
+            .map_err(|_| format!("invalid coordinate '{}'", word))?;
+    }
+    Ok(pos)
+}
+
+impl MapFile {
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut seed = None;
+        let mut rows = Vec::new();
+        let mut voxels = Vec::new();
+        let mut water = Vec::new();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: String| format!("{}:{}: {}", name, line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["seed", number] => {
+                    seed = Some(
+                        number
+                            .parse()
+                            .map_err(|_| error(format!("invalid seed '{}'", number)))?,
+                    )
+                }
+                ["row", columns @ ..] => rows.push(
+                    columns
+                        .iter()
+                        .map(|word| parse_node(word))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(error)?,
+                ),
+                ["voxel", x, y, z, block] => {
+                    let pos = parse_pos(&[x, y, z]).map_err(error)?;
+                    let voxel = match *block {
+                        "Air" => WorldVoxel::Air,
+                        block => WorldVoxel::Solid(block.parse().map_err(error)?),
+                    };
+                    voxels.push((pos, voxel));
+                }
+                ["water", x, y, z, material] => {
+                    let pos = parse_pos(&[x, y, z]).map_err(error)?;
+                    let voxel = match *material {
+                        "Air" => WorldVoxel::Air,
+                        material => WorldVoxel::Solid(material.parse().map_err(|_| {
+                            error(format!("invalid water material '{}'", material))
+                        })?),
+                    };
+                    water.push((pos, voxel));
+                }
+                _ => return Err(error(format!("can't parse '{}'", line))),
+            }
+        }
+        let map = Map::from_rows(rows).map_err(|err| format!("{}: {}", name, err))?;
+        Ok(Self {
+            map,
+            seed,
+            voxels,
+            water,
+        })
+    }
+
+    pub fn load(file: impl AsRef<Path>) -> Result<Self, String> {
+        let file = file.as_ref();
+        let source = fs::read_to_string(file)
+            .map_err(|err| format!("can't read {}: {}", file.display(), err))?;
+        Self::parse(&file.display().to_string(), &source)
+    }
+
+    pub fn save(&self, file: impl AsRef<Path>) -> Result<(), String> {
+        let file = file.as_ref();
+        file.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(file, self.to_string()))
+            .map_err(|err| format!("can't write {}: {}", file.display(), err))
+    }
+}
+
+impl fmt::Display for MapFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {}", seed)?;
+        }
+        writeln!(f, "# row surface:height:moisture...")?;
+        for row in &self.map.map {
+            write!(f, "row")?;
+            for node in row {
+                write!(
+                    f,
+                    " {:?}:{}:{}",
+                    node.surface_type, node.height, node.moisture
+                )?;
+            }
+            writeln!(f)?;
+        }
+        for (pos, voxel) in &self.voxels {
+            match voxel {
+                WorldVoxel::Solid(block) => {
+                    writeln!(f, "voxel {} {} {} {:?}", pos.x, pos.y, pos.z, block)?
+                }
+                _ => writeln!(f, "voxel {} {} {} Air", pos.x, pos.y, pos.z)?,
+            }
+        }
+        for (pos, voxel) in &self.water {
+            match voxel {
+                WorldVoxel::Solid(material) => {
+                    writeln!(f, "water {} {} {} {}", pos.x, pos.y, pos.z, material)?
+                }
+                _ => writeln!(f, "water {} {} {} Air", pos.x, pos.y, pos.z)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_parses_back() {
+        let sut = MapFile {
+            map: Map::test_map(),
+            seed: Some(42),
+            voxels: vec![
+                (IVec3::new(1, 9, -2), WorldVoxel::Solid(BlockTexture::Lava)),
+                (IVec3::new(0, 5, 0), WorldVoxel::Air),
+            ],
+            water: vec![(IVec3::new(-3, 4, 2), WorldVoxel::Solid(2))],
+        };
+        assert_eq!(MapFile::parse("test", &sut.to_string()), Ok(sut));
+    }
+
+    #[test]
+    fn parse_reports_line() {
+        let source = "row Grass:1:0 Sand:0:0\n\nrow Grass:1:0 Mud:0:0\n";
+        assert_eq!(
+            MapFile::parse("test.map", source),
+            Err("test.map:3: unknown surface type 'Mud'".into())
+        );
+        assert_eq!(
+            MapFile::parse("test.map", "seed 1\n"),
+            Err("test.map: a map needs at least one column".into())
+        );
+    }
+}
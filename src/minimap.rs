@@ -0,0 +1,337 @@
+use crate::camera::CameraMode;
+use crate::editing::{VoxelChanged, WaterChanged};
+use crate::input::{Action, Actions};
+use crate::map::Map;
+use crate::player::Player;
+use crate::textures::{map_shade, BlockTexture};
+use crate::{MyMainWorld, WaterWorld};
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{TextureDimension, TextureFormat};
+use bevy::ui::RelativeCursorPosition;
+use bevy_voxel_world::prelude::*;
+use smooth_bevy_cameras::LookTransform;
+
+/// Width of the minimap on screen in pixels.
+const MINIMAP_WIDTH: f32 = 200.0;
+/// How far the view frustum lines reach, in voxels.
+const FRUSTUM_LENGTH: f32 = 48.0;
+const CAMERA_COLOR: [u8; 4] = [255, 40, 40, 255];
+const FRUSTUM_COLOR: [u8; 4] = [255, 240, 120, 255];
+/// How far down a column is searched for its new top when its top block is removed.
+const MAX_DIG: i32 = 64;
+
+/// The color of a column, brighter the higher its top is.
+fn shaded(block: BlockTexture, height: i32) -> [u8; 4] {
+    let shade = map_shade(height, 0);
+    let [r, g, b] = block
+        .map_color()
+        .map(|c| (c as f32 * shade).min(255.0) as u8);
+    [r, g, b, 255]
+}
+
+/// Top down view of the map, a pixel per column. Edits only redraw the columns they touch.
+#[derive(Resource)]
+pub(crate) struct Minimap {
+    min: IVec2,
+    extent: UVec2,
+    /// Height of the top block of every column.
+    tops: Vec<i32>,
+    /// Colors of the columns, without the camera drawn on top.
+    terrain: Vec<u8>,
+    image: Handle<Image>,
+    dirty: bool,
+    /// Camera position and direction the image was last drawn with.
+    drawn: Option<(Vec3, Vec3)>,
+}
+
+impl Minimap {
+    fn from_map(map: &Map, image: Handle<Image>) -> Self {
+        let (min, extent) = (map.min_corner(), map.extent());
+        let mut minimap = Self {
+            min,
+            extent,
+            tops: vec![0; (extent.x * extent.y) as usize],
+            terrain: vec![0; (extent.x * extent.y * 4) as usize],
+            image,
+            dirty: true,
+            drawn: None,
+        };
+        for x in 0..extent.x as i32 {
+            for z in 0..extent.y as i32 {
+                let column = min + IVec2::new(x, z);
+                if let Some(node) = map.get(IVec3::new(column.x, 0, column.y)) {
+                    minimap.set_column(column, node.height as i32, node.surface_type.block());
+                }
+            }
+        }
+        minimap
+    }
+
+    fn index(&self, column: IVec2) -> Option<usize> {
+        let pixel = column - self.min;
+        let inside = pixel.cmpge(IVec2::ZERO).all() && pixel.cmplt(self.extent.as_ivec2()).all();
+        inside.then(|| (pixel.y * self.extent.x as i32 + pixel.x) as usize)
+    }
+
+    fn set_column(&mut self, column: IVec2, top: i32, block: BlockTexture) {
+        if let Some(index) = self.index(column) {
+            self.tops[index] = top;
+            self.terrain[index * 4..index * 4 + 4].copy_from_slice(&shaded(block, top));
+            self.dirty = true;
+        }
+    }
+
+    /// Redraw the column of `pos` after the voxel there changed. `block_at` is the solid block at a
+    /// height of the column, used to find the new top when the top is removed.
+    fn voxel_changed(
+        &mut self,
+        pos: IVec3,
+        solid: Option<BlockTexture>,
+        block_at: impl Fn(i32) -> Option<BlockTexture>,
+    ) {
+        let column = pos.xz();
+        let Some(index) = self.index(column) else {
+            return;
+        };
+        let top = self.tops[index];
+        match solid {
+            Some(block) if pos.y >= top => self.set_column(column, pos.y, block),
+            None if pos.y == top => {
+                let (y, block) = (top - MAX_DIG..top)
+                    .rev()
+                    .find_map(|y| block_at(y).map(|block| (y, block)))
+                    .unwrap_or((top - MAX_DIG, BlockTexture::FullBrick));
+                self.set_column(column, y, block);
+            }
+            _ => {}
+        }
+    }
+
+    fn plot(&self, pixels: &mut [u8], at: Vec2, color: [u8; 4]) {
+        if let Some(index) = self.index(at.floor().as_ivec2()) {
+            pixels[index * 4..index * 4 + 4].copy_from_slice(&color);
+        }
+    }
+
+    /// The terrain with the camera and the edges of its view drawn on top. Positions are in
+    /// voxels, `half_fov` is half the horizontal field of view.
+    fn render(&self, eye: Vec3, direction: Vec3, half_fov: f32) -> Vec<u8> {
+        let mut pixels = self.terrain.clone();
+        let eye = eye.xz();
+        let forward = direction.xz().normalize_or(Vec2::NEG_Y);
+        for side in [-half_fov, half_fov] {
+            let edge = Vec2::from_angle(side).rotate(forward);
+            for step in 0..FRUSTUM_LENGTH as usize {
+                self.plot(&mut pixels, eye + edge * step as f32, FRUSTUM_COLOR);
+            }
+        }
+        for offset in [IVec2::ZERO, IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            self.plot(&mut pixels, eye + offset.as_vec2(), CAMERA_COLOR);
+        }
+        pixels
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct MinimapNode;
+
+pub(crate) fn setup_minimap(
+    mut commands: Commands,
+    world: Res<MyMainWorld>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let extent = world.static_map.extent();
+    let mut image = Image::new_fill(
+        world.static_map.image_size(),
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+
+    commands.spawn((
+        MinimapNode,
+        ImageNode::new(image.clone()),
+        RelativeCursorPosition::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(64.0),
+            right: Val::Px(8.0),
+            width: Val::Px(MINIMAP_WIDTH),
+            height: Val::Px(MINIMAP_WIDTH * extent.y as f32 / extent.x as f32),
+            border: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        BorderColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+    ));
+    commands.insert_resource(Minimap::from_map(&world.static_map, image));
+}
+
+/// Redraw the columns of edited voxels, water counts as the top of a column as well. All of
+/// the minimap is drawn again when the map is replaced.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_minimap_terrain(
+    mut minimap: ResMut<Minimap>,
+    mut changes: EventReader<VoxelChanged>,
+    mut water_changes: EventReader<WaterChanged>,
+    voxel_world: VoxelWorld<MyMainWorld>,
+    water_world: VoxelWorld<WaterWorld>,
+    world: Res<MyMainWorld>,
+    mut images: ResMut<Assets<Image>>,
+    mut nodes: Query<&mut Node, With<MinimapNode>>,
+) {
+    if world.is_changed() {
+        *minimap = Minimap::from_map(&world.static_map, minimap.image.clone());
+        if let Some(image) = images.get_mut(&minimap.image) {
+            image.resize(world.static_map.image_size());
+        }
+        for mut node in nodes.iter_mut() {
+            node.height =
+                Val::Px(MINIMAP_WIDTH * minimap.extent.y as f32 / minimap.extent.x as f32);
+        }
+    }
+    let block_at = |pos: IVec3| match voxel_world.get_voxel(pos) {
+        WorldVoxel::Solid(block) => Some(block),
+        _ if matches!(water_world.get_voxel(pos), WorldVoxel::Solid(_)) => {
+            Some(BlockTexture::WaterBrick)
+        }
+        WorldVoxel::Unset => match world.static_map.voxel_at(pos) {
+            WorldVoxel::Solid(block) => Some(block),
+            _ => None,
+        },
+        WorldVoxel::Air => None,
+    };
+    let changed = changes
+        .read()
+        .map(|change| match change.after {
+            WorldVoxel::Solid(block) => (change.pos, Some(block)),
+            _ => (change.pos, None),
+        })
+        .chain(water_changes.read().map(|change| match change.after {
+            WorldVoxel::Solid(_) => (change.pos, Some(BlockTexture::WaterBrick)),
+            _ => (change.pos, None),
+        }));
+    for (pos, solid) in changed {
+        // Removed water might have been above terrain that is still there
+        let solid = solid.or_else(|| block_at(pos));
+        minimap.voxel_changed(pos, solid, |y| block_at(pos.with_y(y)));
+    }
+}
+
+/// Draw the camera and its view frustum on the minimap when it moved or the terrain changed.
+pub(crate) fn draw_minimap(
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<(&LookTransform, &Projection), With<Player>>,
+) {
+    let Ok((look, projection)) = cameras.get_single() else {
+        return;
+    };
+    let direction = look.look_direction().unwrap_or(Vec3::NEG_Z);
+    if !minimap.dirty && minimap.drawn == Some((look.eye, direction)) {
+        return;
+    }
+    let half_fov = match projection {
+        Projection::Perspective(perspective) => {
+            ((perspective.fov / 2.0).tan() * perspective.aspect_ratio).atan()
+        }
+        Projection::Orthographic(_) => 0.0,
+    };
+    let pixels = minimap.render(look.eye, direction, half_fov);
+    if let Some(image) = images.get_mut(&minimap.image) {
+        image.data = pixels;
+    }
+    minimap.dirty = false;
+    minimap.drawn = Some((look.eye, direction));
+}
+
+/// Placing on the minimap moves the camera above that column instead. The action is used up so
+/// it doesn't place a block as well.
+pub(crate) fn click_minimap(
+    mut actions: ResMut<Actions>,
+    minimap: Res<Minimap>,
+    world: Res<MyMainWorld>,
+    mode: Res<CameraMode>,
+    nodes: Query<&RelativeCursorPosition, With<MinimapNode>>,
+    mut cameras: Query<(&mut LookTransform, &mut Player)>,
+) {
+    if !actions.just_pressed(Action::Place) {
+        return;
+    }
+    let Some(normalized) = nodes
+        .iter()
+        .find(|cursor| cursor.mouse_over())
+        .and_then(|cursor| cursor.normalized)
+    else {
+        return;
+    };
+    actions.consume(Action::Place);
+
+    let column = world.static_map.column_at(normalized);
+    let top = minimap.index(column).map_or(0, |index| minimap.tops[index]);
+    for (mut look, mut player) in cameras.iter_mut() {
+        let to = Vec3::new(column.x as f32 + 0.5, 0.0, column.y as f32 + 0.5);
+        let mut delta = (to - look.eye).with_y(0.0);
+        // Keep the camera out of the ground
+        delta.y = (top as f32 + 2.0 - look.eye.y).max(0.0);
+        look.eye += delta;
+        look.target += delta;
+        if *mode == CameraMode::FirstPerson {
+            *player = Player::looking_from(&look);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sut() -> Minimap {
+        Minimap::from_map(&Map::test_map(), Handle::default())
+    }
+
+    #[test]
+    fn columns_from_map() {
+        let sut = sut();
+        // The first column of the test map is snow at height 8
+        let corner = sut.index(IVec2::new(-5, -5)).unwrap();
+        assert_eq!(sut.tops[corner], 8);
+        assert_eq!(
+            &sut.terrain[corner * 4..corner * 4 + 4],
+            &shaded(BlockTexture::SnowyBrick, 8)
+        );
+        assert_eq!(sut.index(IVec2::new(6, 0)), None);
+    }
+
+    #[test]
+    fn edits_update_column_top() {
+        let mut sut = sut();
+        let pos = IVec3::new(-5, 12, -5);
+        sut.voxel_changed(pos, Some(BlockTexture::Lava), |_| None);
+        let corner = sut.index(pos.xz()).unwrap();
+        assert_eq!(sut.tops[corner], 12);
+        // Removing the top finds the highest block left below it
+        sut.voxel_changed(pos, None, |y| (y <= 8).then_some(BlockTexture::StoneBrick));
+        assert_eq!(sut.tops[corner], 8);
+        assert_eq!(
+            &sut.terrain[corner * 4..corner * 4 + 4],
+            &shaded(BlockTexture::StoneBrick, 8)
+        );
+        // Digging below the top doesn't show
+        sut.voxel_changed(pos.with_y(3), None, |_| None);
+        assert_eq!(sut.tops[corner], 8);
+    }
+
+    #[test]
+    fn render_draws_camera() {
+        let sut = sut();
+        let pixels = sut.render(Vec3::new(0.5, 20.0, 0.5), Vec3::NEG_Z, 0.5);
+        let center = sut.index(IVec2::ZERO).unwrap();
+        assert_eq!(&pixels[center * 4..center * 4 + 4], &CAMERA_COLOR);
+        assert_eq!(pixels.len(), sut.terrain.len());
+    }
+}
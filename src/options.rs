@@ -0,0 +1,293 @@
+use crate::history::DEFAULT_HISTORY_SIZE;
+use crate::map::{GenParams, Size};
+use bevy::prelude::*;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: voxel_demo [options]
+
+Map:
+  --size <WxD>           Columns of the generated map along x and z [default: 200x200]
+  --seed <n>             Seed of the terrain noise [default: 1234]
+  --map <file>           Load a saved map instead of generating one
+  --octaves <n>          Layers of noise adding detail, 1 to 32 [default: 5]
+  --frequency <f>        How quickly the terrain changes [default: 1.1]
+  --lacunarity <f>       Frequency of each layer relative to the one before [default: 2.8]
+  --persistence <f>      Strength of each layer relative to the one before [default: 0.4]
+  --amplitude <f>        Height of the terrain noise in voxels [default: 50]
+
+Window:
+  --window <WxH>         Window size in pixels [default: 1280x720]
+  --vsync <on|off>       Wait for the display before showing a frame [default: on]
+
+Camera:
+  --camera <x,y,z>       Where the camera starts [default: 10,10,10]
+  --look-at <x,y,z>      What the camera starts looking at [default: 0,0,0]
+
+Editing:
+  --history <n>          Edits that can be undone, 1 to 10000 [default: 100]
+
+  -h, --help             Show this help
+";
+
+/// Largest map size along either axis.
+pub const MAX_MAP_SIZE: u32 = 4096;
+const MAX_WINDOW_SIZE: u32 = 16384;
+/// Most octaves the noise supports.
+pub const MAX_OCTAVES: usize = 32;
+const MAX_HISTORY_SIZE: usize = 10000;
+
+/// Settings from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub size: Size,
+    pub params: GenParams,
+    pub map: Option<PathBuf>,
+    pub window: UVec2,
+    pub vsync: bool,
+    pub camera: Vec3,
+    pub look_at: Vec3,
+    /// Number of edits kept for undo.
+    pub history: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            size: Size::new(200, 200),
+            params: GenParams::default(),
+            map: None,
+            window: UVec2::new(1280, 720),
+            vsync: true,
+            camera: Vec3::splat(10.0),
+            look_at: Vec3::ZERO,
+            history: DEFAULT_HISTORY_SIZE,
+        }
+    }
+}
+
+pub fn wants_help(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "-h" || arg == "--help")
+}
+
+fn number<T: FromStr>(value: &str, expected: &str) -> Result<T, String> {
+    value.parse().map_err(|_| expected.to_string())
+}
+
+fn positive(value: &str) -> Result<f64, String> {
+    match number::<f64>(value, "expected a number") {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err("expected a number above 0".into()),
+    }
+}
+
+/// Two sizes joined by an x, as in 200x100.
+fn dimensions(value: &str, max: u32) -> Result<UVec2, String> {
+    let expected = || format!("expected two sizes from 1 to {} such as 200x100", max);
+    let (a, b) = value.split_once('x').ok_or_else(expected)?;
+    let a: u32 = number(a, &expected())?;
+    let b: u32 = number(b, &expected())?;
+    if !(1..=max).contains(&a) || !(1..=max).contains(&b) {
+        return Err(expected());
+    }
+    Ok(UVec2::new(a, b))
+}
+
+fn position(value: &str) -> Result<Vec3, String> {
+    let expected = "expected x,y,z such as 10,20,-5";
+    let coordinates = value
+        .split(',')
+        .map(|coordinate| number::<f32>(coordinate.trim(), expected))
+        .collect::<Result<Vec<_>, _>>()?;
+    match coordinates.as_slice() {
+        [x, y, z] if coordinates.iter().all(|c| c.is_finite()) => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err(expected.into()),
+    }
+}
+
+/// A flag and its value.
+pub type Flag = (String, String);
+
+/// The arguments without a flag, and the flags with their values. A flag is followed by its
+/// value, or joined to it by `=`.
+pub fn split_args(args: &[String]) -> Result<(Vec<String>, Vec<Flag>), String> {
+    let mut positional = Vec::new();
+    let mut flags = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, value.to_string()),
+            None => match args.next() {
+                Some(value) => (arg.as_str(), value.clone()),
+                None => return Err(format!("{} needs a value, see --help", arg)),
+            },
+        };
+        flags.push((flag.to_string(), value));
+    }
+    Ok((positional, flags))
+}
+
+fn invalid(flag: &str, value: &str, err: String) -> String {
+    format!("invalid value '{}' for {}: {}", value, flag, err)
+}
+
+/// Apply a flag of the map generation, false if it's another flag.
+pub fn set_map_flag(
+    flag: &str,
+    value: &str,
+    size: &mut Size,
+    params: &mut GenParams,
+) -> Result<bool, String> {
+    let invalid = |err: String| invalid(flag, value, err);
+    match flag {
+        "--size" => {
+            let extent = dimensions(value, MAX_MAP_SIZE).map_err(invalid)?;
+            *size = Size::new(extent.x, extent.y);
+        }
+        "--seed" => {
+            params.seed =
+                number(value, "expected a whole number from 0 to 4294967295").map_err(invalid)?
+        }
+        "--octaves" => {
+            params.octaves = match number(value, "") {
+                Ok(octaves) if (1..=MAX_OCTAVES).contains(&octaves) => octaves,
+                _ => return Err(invalid(format!("expected 1 to {}", MAX_OCTAVES))),
+            }
+        }
+        "--frequency" => params.frequency = positive(value).map_err(invalid)?,
+        "--lacunarity" => params.lacunarity = positive(value).map_err(invalid)?,
+        "--persistence" => params.persistence = positive(value).map_err(invalid)?,
+        "--amplitude" => params.amplitude = positive(value).map_err(invalid)?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+impl Options {
+    /// The options from the arguments after the program name.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let (positional, flags) = split_args(args)?;
+        if let Some(arg) = positional.first() {
+            return Err(format!("unexpected argument '{}', see --help", arg));
+        }
+        for (flag, value) in &flags {
+            if set_map_flag(flag, value, &mut options.size, &mut options.params)? {
+                continue;
+            }
+            let invalid = |err: String| invalid(flag, value, err);
+            match flag.as_str() {
+                "--map" => options.map = Some(PathBuf::from(value)),
+                "--window" => {
+                    options.window = dimensions(value, MAX_WINDOW_SIZE).map_err(invalid)?
+                }
+                "--vsync" => {
+                    options.vsync = match value.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(invalid("expected on or off".into())),
+                    }
+                }
+                "--camera" => options.camera = position(value).map_err(invalid)?,
+                "--look-at" => options.look_at = position(value).map_err(invalid)?,
+                "--history" => {
+                    options.history = match number(value, "") {
+                        Ok(size) if (1..=MAX_HISTORY_SIZE).contains(&size) => size,
+                        _ => return Err(invalid(format!("expected 1 to {}", MAX_HISTORY_SIZE))),
+                    }
+                }
+                _ => return Err(format!("unknown option '{}', see --help", flag)),
+            }
+        }
+        if options.camera == options.look_at {
+            return Err("--camera and --look-at can't be the same position".into());
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parse_flags() {
+        let sut = parse("--size 64x32 --seed=7 --octaves 8 --vsync off --camera 1,2.5,-3").unwrap();
+        assert_eq!(sut.size, Size::new(64, 32));
+        assert_eq!(sut.params.seed, 7);
+        assert_eq!(sut.params.octaves, 8);
+        assert!(!sut.vsync);
+        assert_eq!(sut.camera, Vec3::new(1.0, 2.5, -3.0));
+        assert_eq!(sut.window, Options::default().window);
+        assert_eq!(parse("--history 500").unwrap().history, 500);
+        assert_eq!(
+            parse("--history 0"),
+            Err("invalid value '0' for --history: expected 1 to 10000".into())
+        );
+        assert_eq!(parse(""), Ok(Options::default()));
+    }
+
+    #[test]
+    fn errors_name_flag_and_value() {
+        assert_eq!(
+            parse("--seed -1"),
+            Err(
+                "invalid value '-1' for --seed: expected a whole number from 0 to 4294967295"
+                    .into()
+            )
+        );
+        assert_eq!(
+            parse("--size 200"),
+            Err(
+                "invalid value '200' for --size: expected two sizes from 1 to 4096 such as 200x100"
+                    .into()
+            )
+        );
+        assert_eq!(
+            parse("--frequency 0"),
+            Err("invalid value '0' for --frequency: expected a number above 0".into())
+        );
+        assert_eq!(
+            parse("--window"),
+            Err("--window needs a value, see --help".into())
+        );
+        assert_eq!(
+            parse("--sed 1"),
+            Err("unknown option '--sed', see --help".into())
+        );
+        assert_eq!(
+            parse("--seed 1 extra"),
+            Err("unexpected argument 'extra', see --help".into())
+        );
+        assert_eq!(
+            parse("--camera 0,0,0"),
+            Err("--camera and --look-at can't be the same position".into())
+        );
+    }
+
+    #[test]
+    fn split_positional_and_flags() {
+        let args: Vec<String> = ["in.map", "--scale=4", "out.png", "--camera", "-1,2,3"]
+            .map(String::from)
+            .to_vec();
+        let (positional, flags) = split_args(&args).unwrap();
+        assert_eq!(positional, ["in.map", "out.png"]);
+        assert_eq!(
+            flags,
+            [
+                ("--scale".to_string(), "4".to_string()),
+                ("--camera".to_string(), "-1,2,3".to_string())
+            ]
+        );
+    }
+}
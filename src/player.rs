@@ -0,0 +1,292 @@
+use crate::blocks::{is_collidable, is_liquid, BlockProperties};
+use crate::camera::CameraMode;
+use crate::input::{Action, Actions};
+use crate::terrain::terrain_voxel;
+use crate::{MyMainWorld, WaterWorld};
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+use smooth_bevy_cameras::LookTransform;
+
+const HALF_WIDTH: f32 = 0.3;
+const HEIGHT: f32 = 1.8;
+const EYE_HEIGHT: f32 = 1.6;
+/// Ledges up to this high are walked onto without jumping.
+const STEP_HEIGHT: f32 = 1.0;
+const GRAVITY: f32 = 24.0;
+const JUMP_SPEED: f32 = 8.0;
+const WALK_SPEED: f32 = 5.0;
+/// How fast the walking velocity follows the move actions on a block of friction 1, per second.
+const GRIP: f32 = 20.0;
+/// Friction used to steer while jumping or falling, a little control but mostly momentum.
+const AIR_FRICTION: f32 = 0.1;
+const SWIM_SPEED: f32 = 3.0;
+/// Fraction of the velocity kept per second while in a liquid.
+const WATER_DRAG: f32 = 0.1;
+const LOOK_SENSITIVITY: f32 = 0.003;
+/// Keeps a body touching a face from counting as overlapping the voxel behind it.
+const SKIN: f32 = 1e-3;
+
+/// The first person player, walking the terrain with the camera at eye height.
+#[derive(Component, Default)]
+pub(crate) struct Player {
+    /// Center of the feet.
+    pub(crate) position: Vec3,
+    velocity: Vec3,
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+    in_liquid: bool,
+}
+
+/// The voxels the body overlaps with its feet at `position`.
+fn overlapped_voxels(position: Vec3) -> impl Iterator<Item = IVec3> {
+    let min = (position - Vec3::new(HALF_WIDTH, 0.0, HALF_WIDTH))
+        .floor()
+        .as_ivec3();
+    let max = (position + Vec3::new(HALF_WIDTH, HEIGHT, HALF_WIDTH) - Vec3::splat(SKIN))
+        .floor()
+        .as_ivec3();
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+    })
+}
+
+fn collides(position: Vec3, solid: &impl Fn(IVec3) -> bool) -> bool {
+    overlapped_voxels(position).any(solid)
+}
+
+/// Move along a single axis, stopping flush against the first solid voxel. Returns whether the
+/// move was blocked.
+fn move_axis(position: &mut Vec3, axis: usize, delta: f32, solid: &impl Fn(IVec3) -> bool) -> bool {
+    // Steps below a voxel so no voxel is skipped
+    let steps = (delta.abs() / 0.5).ceil().max(1.0) as usize;
+    let step = delta / steps as f32;
+    let (low, high) = (
+        [-HALF_WIDTH, 0.0, -HALF_WIDTH],
+        [HALF_WIDTH, HEIGHT, HALF_WIDTH],
+    );
+    for _ in 0..steps {
+        let mut moved = *position;
+        moved[axis] += step;
+        if !collides(moved, solid) {
+            *position = moved;
+            continue;
+        }
+        // Snap to the face of the voxel that was hit
+        position[axis] = if step > 0.0 {
+            (moved[axis] + high[axis]).floor() - high[axis] - SKIN
+        } else {
+            (moved[axis] + low[axis]).floor() + 1.0 - low[axis]
+        };
+        return true;
+    }
+    false
+}
+
+/// Move the body by `delta`, sliding along walls. Bodies on the ground step up onto low ledges.
+/// Returns the new position and whether the vertical move was blocked by the ground or a ceiling.
+fn move_body(
+    position: Vec3,
+    delta: Vec3,
+    on_ground: bool,
+    solid: &impl Fn(IVec3) -> bool,
+) -> (Vec3, bool) {
+    let mut moved = position;
+    for axis in [0, 2] {
+        let before = moved;
+        if move_axis(&mut moved, axis, delta[axis], solid) && on_ground {
+            let mut stepped = before + Vec3::Y * STEP_HEIGHT;
+            if !collides(stepped, solid) && !move_axis(&mut stepped, axis, delta[axis], solid) {
+                // Settle back down onto the ledge
+                move_axis(&mut stepped, 1, -STEP_HEIGHT, solid);
+                moved = stepped;
+            }
+        }
+    }
+    let blocked = move_axis(&mut moved, 1, delta.y, solid);
+    (moved, blocked)
+}
+
+impl Player {
+    /// A player standing where the camera eye is, looking the same way.
+    pub(crate) fn looking_from(look: &LookTransform) -> Self {
+        let direction = look.look_direction().unwrap_or(Vec3::NEG_Z);
+        Self {
+            position: look.eye - Vec3::Y * EYE_HEIGHT,
+            yaw: (-direction.x).atan2(-direction.z),
+            pitch: direction.y.clamp(-1.0, 1.0).asin(),
+            ..default()
+        }
+    }
+}
+
+/// Flat forward and right directions for the yaw.
+fn walk_axes(yaw: f32) -> (Vec3, Vec3) {
+    let forward = Vec3::new(-yaw.sin(), 0.0, -yaw.cos());
+    let right = Vec3::new(-forward.z, 0.0, forward.x);
+    (forward, right)
+}
+
+/// Ease the horizontal velocity towards the walking velocity, slower on slippery blocks.
+fn walk_velocity(velocity: Vec2, target: Vec2, friction: f32, dt: f32) -> Vec2 {
+    velocity.lerp(target, (friction * GRIP * dt).min(1.0))
+}
+
+/// Walk, jump or swim up, and look around while the look action is held.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn first_person_controller(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    mut motion: EventReader<MouseMotion>,
+    mode: Res<CameraMode>,
+    voxel_world: VoxelWorld<MyMainWorld>,
+    water_world: VoxelWorld<WaterWorld>,
+    world: Res<MyMainWorld>,
+    mut players: Query<(&mut Player, &mut LookTransform)>,
+) {
+    let looked: Vec2 = motion.read().map(|ev| ev.delta).sum();
+    if *mode != CameraMode::FirstPerson {
+        return;
+    }
+    let get_voxel = voxel_world.get_voxel_fn();
+    let map = &world.static_map;
+    // Chunks that haven't spawned yet still have the generated terrain to stand on
+    let voxel = |pos| match get_voxel(pos) {
+        WorldVoxel::Unset => terrain_voxel(map, pos),
+        voxel => voxel,
+    };
+    let solid = |pos| is_collidable(voxel(pos));
+    let dt = time.delta_secs().min(0.1);
+
+    for (mut player, mut look) in players.iter_mut() {
+        if actions.pressed(Action::Look) {
+            player.yaw -= looked.x * LOOK_SENSITIVITY;
+            player.pitch = (player.pitch - looked.y * LOOK_SENSITIVITY).clamp(-1.5, 1.5);
+        }
+
+        let (forward, right) = walk_axes(player.yaw);
+        let mut walk = Vec3::ZERO;
+        for (action, direction) in [
+            (Action::MoveForward, forward),
+            (Action::MoveBack, -forward),
+            (Action::MoveRight, right),
+            (Action::MoveLeft, -right),
+        ] {
+            if actions.pressed(action) {
+                walk += direction;
+            }
+        }
+        let jump = actions.pressed(Action::Jump);
+
+        let middle = (player.position + Vec3::Y * HEIGHT / 2.0)
+            .floor()
+            .as_ivec3();
+        player.in_liquid = matches!(water_world.get_voxel(middle), WorldVoxel::Solid(_))
+            || is_liquid(map.voxel_at(middle));
+
+        if player.in_liquid {
+            let swim = walk.normalize_or_zero() * SWIM_SPEED;
+            player.velocity.x = swim.x;
+            player.velocity.z = swim.z;
+            player.velocity.y -= GRAVITY * 0.2 * dt;
+            if jump {
+                player.velocity.y = SWIM_SPEED;
+            }
+            player.velocity.y *= WATER_DRAG.powf(dt);
+        } else {
+            let walk = walk.normalize_or_zero() * WALK_SPEED;
+            let friction = if player.on_ground {
+                let below = (player.position - Vec3::Y * 0.5).floor().as_ivec3();
+                BlockProperties::of(voxel(below)).friction
+            } else {
+                AIR_FRICTION
+            };
+            let horizontal = walk_velocity(player.velocity.xz(), walk.xz(), friction, dt);
+            player.velocity.x = horizontal.x;
+            player.velocity.z = horizontal.y;
+            player.velocity.y -= GRAVITY * dt;
+            if jump && player.on_ground {
+                player.velocity.y = JUMP_SPEED;
+            }
+        }
+
+        let (position, blocked) = move_body(
+            player.position,
+            player.velocity * dt,
+            player.on_ground,
+            &solid,
+        );
+        player.on_ground = blocked && player.velocity.y <= 0.0;
+        if blocked {
+            player.velocity.y = 0.0;
+        }
+        player.position = position;
+
+        let direction =
+            Quat::from_euler(EulerRot::YXZ, player.yaw, player.pitch, 0.0) * Vec3::NEG_Z;
+        look.eye = player.position + Vec3::Y * EYE_HEIGHT;
+        look.target = look.eye + direction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::BlockTexture;
+
+    /// Flat floor at y = 0 with a one voxel step at x = 2 and a two voxel wall at x = 5.
+    fn solid(pos: IVec3) -> bool {
+        pos.y <= 0 || (pos.x == 2 && pos.y == 1) || (pos.x == 5 && pos.y <= 2)
+    }
+
+    #[test]
+    fn falls_onto_floor() {
+        let (position, landed) =
+            move_body(Vec3::new(0.5, 3.0, 0.5), Vec3::NEG_Y * 2.5, false, &solid);
+        assert!(landed);
+        assert!((position.y - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn steps_up_one_voxel() {
+        let (position, landed) = move_body(
+            Vec3::new(1.5, 1.0, 0.5),
+            Vec3::new(0.6, -0.01, 0.0),
+            true,
+            &solid,
+        );
+        assert!(landed);
+        assert!((position.y - 2.0).abs() < 1e-4);
+        assert!((position.x - 2.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn wall_blocks_without_step() {
+        let (position, _) = move_body(
+            Vec3::new(4.5, 1.0, 0.5),
+            Vec3::new(1.0, -0.01, 0.0),
+            true,
+            &solid,
+        );
+        assert!(position.x < 5.0 - HALF_WIDTH);
+        assert!((position.y - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn slippery_blocks_are_slower_to_walk_on() {
+        let speed_after = |block: BlockTexture, seconds: f32| {
+            let friction = block.properties().friction;
+            let mut velocity = Vec2::ZERO;
+            for _ in 0..(seconds * 60.0) as usize {
+                velocity = walk_velocity(velocity, Vec2::X * WALK_SPEED, friction, 1.0 / 60.0);
+            }
+            velocity.x
+        };
+        assert!(speed_after(BlockTexture::StoneBrick, 0.5) > WALK_SPEED * 0.99);
+        assert!(
+            speed_after(BlockTexture::SnowyBrick, 0.1) < speed_after(BlockTexture::StoneBrick, 0.1)
+        );
+        assert!(speed_after(BlockTexture::SnowyBrick, 0.1) > 0.0);
+    }
+}
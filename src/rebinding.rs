@@ -0,0 +1,187 @@
+use crate::input::{
+    Action, Actions, Binding, Button, InputMap, Modifiers, BINDINGS_FILE, GAMEPAD_BUTTONS, KEYS,
+    MOUSE_BUTTONS,
+};
+use bevy::prelude::*;
+use std::fmt::Write;
+
+/// Number of actions listed at once.
+const VISIBLE_ROWS: usize = 24;
+
+/// The screen listing the actions with their bindings. Up and Down pick an action, Enter
+/// replaces its bindings with the next button pressed, Tab adds one, Delete clears them and
+/// Escape closes the screen.
+#[derive(Resource, Default)]
+pub(crate) struct Rebinding {
+    open: bool,
+    selected: usize,
+    /// Waiting for a button, adding it to the bindings instead of replacing them.
+    capturing: Option<bool>,
+}
+
+#[derive(Component)]
+pub(crate) struct RebindingText;
+
+pub(crate) fn setup_rebinding(mut commands: Commands) {
+    commands.spawn((
+        RebindingText,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(10.0),
+            left: Val::Percent(30.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+/// The first button pressed this frame with the modifiers held with it.
+fn pressed_binding(
+    keys: &ButtonInput<KeyCode>,
+    mouse: &ButtonInput<MouseButton>,
+    gamepads: &Query<&Gamepad>,
+) -> Option<Binding> {
+    let button = KEYS
+        .into_iter()
+        .find(|key| keys.just_pressed(*key))
+        .map(Button::Key)
+        .or_else(|| {
+            MOUSE_BUTTONS
+                .into_iter()
+                .find(|button| mouse.just_pressed(*button))
+                .map(Button::Mouse)
+        })
+        .or_else(|| {
+            GAMEPAD_BUTTONS
+                .into_iter()
+                .find(|button| gamepads.iter().any(|pad| pad.just_pressed(*button)))
+                .map(Button::Gamepad)
+        })?;
+    Some(Binding {
+        modifiers: Modifiers::held(keys),
+        button,
+    })
+}
+
+fn rebinding_text(rebinding: &Rebinding, map: &InputMap) -> String {
+    let mut text = String::from("Bindings\n\n");
+    let first = rebinding
+        .selected
+        .saturating_sub(VISIBLE_ROWS / 2)
+        .min(Action::ALL.len() - VISIBLE_ROWS);
+    for (index, action) in Action::ALL
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(VISIBLE_ROWS)
+    {
+        let marker = if index == rebinding.selected {
+            ">"
+        } else {
+            " "
+        };
+        let bindings: Vec<String> = map
+            .bindings(*action)
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        let _ = writeln!(text, "{} {:?}: {}", marker, action, bindings.join(", "));
+    }
+    text.push('\n');
+    text.push_str(match rebinding.capturing {
+        Some(_) => "Press a button, Escape cancels",
+        None => "Enter rebinds, Tab adds a binding, Delete clears, Escape closes",
+    });
+    text
+}
+
+/// While the screen is open the buttons are read directly and no actions trigger.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn rebind_actions(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut actions: ResMut<Actions>,
+    mut rebinding: ResMut<Rebinding>,
+    mut map: ResMut<InputMap>,
+    mut screen: Query<(&mut Text, &mut Visibility), With<RebindingText>>,
+) {
+    if !rebinding.open {
+        if actions.just_pressed(Action::OpenBindings) {
+            rebinding.open = true;
+            actions.suspended = true;
+        } else {
+            return;
+        }
+    } else if let Some(adding) = rebinding.capturing {
+        if keys.just_pressed(KeyCode::Escape) {
+            rebinding.capturing = None;
+        } else if let Some(binding) = pressed_binding(&keys, &mouse, &gamepads) {
+            let bindings = map.bindings_mut(Action::ALL[rebinding.selected]);
+            if !adding {
+                bindings.clear();
+            }
+            if !bindings.contains(&binding) {
+                bindings.push(binding);
+            }
+            rebinding.capturing = None;
+            if let Err(err) = map.save(BINDINGS_FILE) {
+                error!("{}", err);
+            }
+        }
+    } else if keys.just_pressed(KeyCode::Escape) {
+        rebinding.open = false;
+        actions.suspended = false;
+    } else if keys.just_pressed(KeyCode::ArrowUp) {
+        rebinding.selected = rebinding.selected.saturating_sub(1);
+    } else if keys.just_pressed(KeyCode::ArrowDown) {
+        rebinding.selected = (rebinding.selected + 1).min(Action::ALL.len() - 1);
+    } else if keys.just_pressed(KeyCode::Enter) {
+        rebinding.capturing = Some(false);
+    } else if keys.just_pressed(KeyCode::Tab) {
+        rebinding.capturing = Some(true);
+    } else if keys.just_pressed(KeyCode::Delete) {
+        map.bindings_mut(Action::ALL[rebinding.selected]).clear();
+        if let Err(err) = map.save(BINDINGS_FILE) {
+            error!("{}", err);
+        }
+    }
+
+    let (mut text, mut visibility) = screen.single_mut();
+    *visibility = if rebinding.open {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    if rebinding.is_changed() || map.is_changed() {
+        text.0 = rebinding_text(&rebinding, &map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_scrolls_to_selection() {
+        let sut = Rebinding {
+            open: true,
+            selected: Action::ALL.len() - 1,
+            capturing: None,
+        };
+        let text = rebinding_text(&sut, &InputMap::default());
+        assert!(text.contains("> ReloadTexturePack: F5\n"));
+        assert!(!text.contains("Quit"));
+        assert_eq!(
+            text.lines().filter(|line| line.contains(':')).count(),
+            VISIBLE_ROWS
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use crate::commands::replace_map;
+use crate::console::run_command;
+use crate::editing::WorldEditor;
+use crate::map::{GenParams, Map, MapNode, NodeType, Size};
+use crate::options::MAX_MAP_SIZE;
+use crate::textures::BlockTexture;
+use crate::{MyMainWorld, WorldGen};
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::WorldVoxel;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position};
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+pub(crate) const SCRIPTS_DIR: &str = "assets/scripts";
+pub(crate) const SCRIPT_EXTENSION: &str = "rhai";
+/// Run at startup if it exists.
+pub(crate) const STARTUP_SCRIPT: &str = "assets/scripts/startup.rhai";
+/// Stops scripts that loop forever.
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// What the functions of a running script work on. The engine's functions have to own what they
+/// share, so the world is moved in for the run and `WorldGuard` moves it back.
+struct Host {
+    world: World,
+    editor: SystemState<WorldEditor<'static>>,
+}
+
+type SharedHost = Rc<RefCell<Host>>;
+
+/// Moves the world back out of the host when the script is done, also when one of the script's
+/// functions panics.
+struct WorldGuard<'w> {
+    world: &'w mut World,
+    host: SharedHost,
+}
+
+impl Drop for WorldGuard<'_> {
+    fn drop(&mut self) {
+        let Host { world, editor } = &mut *self.host.borrow_mut();
+        // Another panic while unwinding would abort
+        if !std::thread::panicking() {
+            editor.apply(world);
+        }
+        std::mem::swap(self.world, world);
+    }
+}
+
+fn node_type(name: &str) -> ScriptResult<NodeType> {
+    Ok(name.parse::<NodeType>()?)
+}
+
+fn block(name: &str) -> ScriptResult<WorldVoxel<BlockTexture>> {
+    match name {
+        "Air" => Ok(WorldVoxel::Air),
+        name => Ok(WorldVoxel::Solid(name.parse::<BlockTexture>()?)),
+    }
+}
+
+fn voxel_name(voxel: WorldVoxel<BlockTexture>) -> String {
+    match voxel {
+        WorldVoxel::Solid(block) => format!("{:?}", block),
+        WorldVoxel::Air => "Air".into(),
+        WorldVoxel::Unset => "Unset".into(),
+    }
+}
+
+fn height(height: i64) -> ScriptResult<i8> {
+    i8::try_from(height).map_err(|_| format!("height {} is outside -128..127", height).into())
+}
+
+fn column(x: i64, z: i64) -> IVec3 {
+    IVec3::new(x as i32, 0, z as i32)
+}
+
+fn map_size(width: i64, depth: i64) -> ScriptResult<Size> {
+    let sides = 1..=MAX_MAP_SIZE as i64;
+    if !sides.contains(&width) || !sides.contains(&depth) {
+        return Err(format!(
+            "map size {}x{} is outside 1x1..{}x{}",
+            width, depth, MAX_MAP_SIZE, MAX_MAP_SIZE
+        )
+        .into());
+    }
+    Ok(Size::new(width as u32, depth as u32))
+}
+
+fn new_map(width: i64, depth: i64) -> ScriptResult<Map> {
+    map_size(width, depth)?;
+    let row = vec![MapNode::default(); depth as usize];
+    Ok(Map::from_rows(vec![row; width as usize])?)
+}
+
+/// The map and noise functions, which don't need the world.
+fn register_map_api(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Map>("Map")
+        .register_fn("new_map", new_map)
+        .register_fn(
+            "generate_map",
+            |width: i64, depth: i64, seed: i64| -> ScriptResult<Map> {
+                let params = GenParams {
+                    seed: seed as u32,
+                    ..default()
+                };
+                Ok(Map::generate(map_size(width, depth)?, &params))
+            },
+        )
+        .register_get("width", |map: &mut Map| map.extent().x as i64)
+        .register_get("depth", |map: &mut Map| map.extent().y as i64)
+        .register_get("min_x", |map: &mut Map| map.min_corner().x as i64)
+        .register_get("min_z", |map: &mut Map| map.min_corner().y as i64)
+        .register_fn("get", |map: &mut Map, x: i64, z: i64| -> Dynamic {
+            match map.get(column(x, z)) {
+                Some(node) => {
+                    let mut fields = rhai::Map::new();
+                    let surface = format!("{:?}", node.surface_type);
+                    fields.insert("surface".into(), surface.into());
+                    fields.insert("height".into(), (node.height as i64).into());
+                    fields.insert("moisture".into(), (node.moisture as i64).into());
+                    fields.into()
+                }
+                None => Dynamic::UNIT,
+            }
+        })
+        .register_fn("height", |map: &mut Map, x: i64, z: i64| {
+            map.get(column(x, z)).map_or(0, |node| node.height as i64)
+        })
+        .register_fn("heights", |map: &mut Map| -> Array {
+            map.map
+                .iter()
+                .map(|row| {
+                    let heights: Array = row.iter().map(|n| (n.height as i64).into()).collect();
+                    heights.into()
+                })
+                .collect()
+        })
+        .register_fn(
+            "set_height",
+            |map: &mut Map, x: i64, z: i64, h: i64| -> ScriptResult<()> {
+                map.set_height(column(x, z), height(h)?);
+                Ok(())
+            },
+        )
+        .register_fn(
+            "set_surface",
+            |map: &mut Map, x: i64, z: i64, surface: &str| -> ScriptResult<()> {
+                map.set_surface(column(x, z), node_type(surface)?);
+                Ok(())
+            },
+        )
+        .register_fn("perlin", |seed: i64, x: f64, z: f64| {
+            Perlin::new(seed as u32).get([x, z])
+        })
+        .register_fn("perlin", |seed: i64, x: f64, y: f64, z: f64| {
+            Perlin::new(seed as u32).get([x, y, z])
+        })
+        .register_fn("fbm", |seed: i64, x: f64, z: f64, octaves: i64| {
+            Fbm::<Perlin>::new(seed as u32)
+                .set_octaves(octaves.clamp(1, 16) as usize)
+                .get([x, z])
+        });
+}
+
+/// The functions reading and changing the world the script runs in.
+fn register_world_api(engine: &mut Engine, host: &SharedHost) {
+    let shared = host.clone();
+    engine.register_fn("map", move || {
+        shared
+            .borrow()
+            .world
+            .resource::<MyMainWorld>()
+            .static_map
+            .as_ref()
+            .clone()
+    });
+    let shared = host.clone();
+    engine.register_fn("apply_map", move |map: Map| {
+        let world = &mut shared.borrow_mut().world;
+        world.resource_mut::<WorldGen>().map_seed = None;
+        replace_map(world, map);
+    });
+    let shared = host.clone();
+    engine.register_fn("get_voxel", move |x: i64, y: i64, z: i64| {
+        let Host { world, editor } = &mut *shared.borrow_mut();
+        let pos = IVec3::new(x as i32, y as i32, z as i32);
+        voxel_name(editor.get_mut(world).get_voxel(pos))
+    });
+    let shared = host.clone();
+    engine.register_fn(
+        "set_voxel",
+        move |x: i64, y: i64, z: i64, name: &str| -> ScriptResult<()> {
+            let voxel = block(name)?;
+            let Host { world, editor } = &mut *shared.borrow_mut();
+            let pos = IVec3::new(x as i32, y as i32, z as i32);
+            editor.get_mut(world).set_voxel(pos, voxel);
+            Ok(())
+        },
+    );
+    let shared = host.clone();
+    engine.register_fn("run", move |line: &str| -> ScriptResult<String> {
+        Ok(run_command(&mut shared.borrow_mut().world, line)?)
+    });
+}
+
+/// An error with the line of the script it happened on, as `name:line: message`.
+fn script_error(name: &str, position: Position, message: impl std::fmt::Display) -> String {
+    match position.line() {
+        Some(line) => format!("{}:{}: {}", name, line, message),
+        None => format!("{}: {}", name, message),
+    }
+}
+
+/// Run a script on the world, returning what it printed.
+pub(crate) fn run_script(world: &mut World, name: &str, source: &str) -> Result<String, String> {
+    let editor = SystemState::<WorldEditor>::new(world);
+    let host = Rc::new(RefCell::new(Host {
+        world: std::mem::take(world),
+        editor,
+    }));
+    let guard = WorldGuard {
+        world,
+        host: host.clone(),
+    };
+    let printed = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let output = printed.clone();
+    engine.on_print(move |text| output.borrow_mut().push(text.to_string()));
+    register_map_api(&mut engine);
+    register_world_api(&mut engine, &host);
+
+    let result = engine
+        .compile(source)
+        .map_err(|err| script_error(name, err.1, &err.0))
+        .and_then(|ast| {
+            engine.run_ast(&ast).map_err(|mut err| {
+                let position = err.take_position();
+                match *err {
+                    // Errors of our functions, without rhai's prefix
+                    EvalAltResult::ErrorRuntime(message, _) => {
+                        script_error(name, position, message)
+                    }
+                    err => script_error(name, position, err),
+                }
+            })
+        });
+
+    drop(guard);
+    let printed = printed.take().join("\n");
+    result.map(|_| printed)
+}
+
+pub(crate) fn run_script_file(world: &mut World, file: &Path) -> Result<String, String> {
+    let source = fs::read_to_string(file)
+        .map_err(|err| format!("can't read {}: {}", file.display(), err))?;
+    run_script(world, &file.display().to_string(), &source)
+}
+
+/// Run the startup script, if there is one.
+pub(crate) fn run_startup_script(world: &mut World) {
+    let file = Path::new(STARTUP_SCRIPT);
+    if !file.exists() {
+        return;
+    }
+    match run_script_file(world, file) {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => info!("{}", output),
+        Err(err) => error!("{}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_edits_map_copy() {
+        let source = "
+            let m = new_map(4, 3);
+            m.set_height(-2, -1, 9);
+            m.set_surface(-2, -1, \"Sand\");
+            let node = m.get(-2, -1);
+            print(`${node.surface} ${node.height} ${m.width}x${m.depth}`);
+            print(m.heights()[0]);
+            print(m.get(10, 10) == ());
+        ";
+        assert_eq!(
+            run_script(&mut World::new(), "test.rhai", source),
+            Ok("Sand 9 4x3\n[9, 0, 0]\ntrue".into())
+        );
+    }
+
+    #[test]
+    fn noise_is_seeded() {
+        let source = "
+            print(perlin(1, 0.3, 0.7) == perlin(1, 0.3, 0.7));
+            print(fbm(1, 0.3, 0.7, 4) != fbm(2, 0.3, 0.7, 4));
+        ";
+        assert_eq!(
+            run_script(&mut World::new(), "test.rhai", source),
+            Ok("true\ntrue".into())
+        );
+    }
+
+    #[test]
+    fn errors_report_line() {
+        let sut = |source| run_script(&mut World::new(), "test.rhai", source).unwrap_err();
+        assert_eq!(
+            sut("let m = new_map(2, 2);\nm.set_surface(0, 0, \"Mud\");"),
+            "test.rhai:2: unknown surface type 'Mud'"
+        );
+        assert!(sut("let a = 1;\n\nlet b = ;").starts_with("test.rhai:3: "));
+        assert_eq!(
+            sut("let m = new_map(2, 2);\nm.set_height(0, 0, 300);"),
+            "test.rhai:2: height 300 is outside -128..127"
+        );
+        assert_eq!(
+            sut("let m = generate_map(-1, 2, 7);"),
+            "test.rhai:1: map size -1x2 is outside 1x1..4096x4096"
+        );
+        assert_eq!(
+            sut("let m = new_map(2, 5000000000);"),
+            "test.rhai:1: map size 2x5000000000 is outside 1x1..4096x4096"
+        );
+    }
+
+    #[test]
+    fn world_is_kept() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        assert!(run_script(&mut world, "test.rhai", "print(1)").is_ok());
+        assert!(world.contains_resource::<Time>());
+    }
+
+    #[test]
+    fn world_is_kept_when_a_function_panics() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        // Without a terrain world `map` panics
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            run_script(&mut world, "test.rhai", "map()")
+        }));
+        assert!(result.is_err());
+        assert!(world.contains_resource::<Time>());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::cursor::{CursorCube, VoxelTrace};
+use crate::editing::WorldEditor;
+use crate::input::{Action, Actions};
+use bevy::math::I64Vec3;
+use bevy::prelude::*;
+use bevy_voxel_world::prelude::*;
+
+/// Most voxels a single fill may change, every voxel of it is written in one frame.
+pub(crate) const MAX_FILL: i64 = 32 * 32 * 32;
+
+/// An axis aligned box of voxels, both corners included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub(crate) min: IVec3,
+    pub(crate) max: IVec3,
+}
+
+impl Region {
+    pub(crate) fn from_corners(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub(crate) fn size(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+
+    /// Number of voxels in the box, without overflowing for any corners.
+    pub(crate) fn volume(&self) -> i64 {
+        let size = self.max.as_i64vec3() - self.min.as_i64vec3() + I64Vec3::ONE;
+        size.x.saturating_mul(size.y).saturating_mul(size.z)
+    }
+
+    pub(crate) fn contains(&self, pos: IVec3) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+
+    /// Whether `pos` is on one of the six faces of the box.
+    pub(crate) fn on_boundary(&self, pos: IVec3) -> bool {
+        self.contains(pos) && (pos.cmpeq(self.min).any() || pos.cmpeq(self.max).any())
+    }
+
+    pub(crate) fn voxels(&self) -> impl Iterator<Item = IVec3> {
+        let Region { min, max } = *self;
+        (min.x..=max.x).flat_map(move |x| {
+            (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+        })
+    }
+}
+
+/// The voxel a `VoxelTrace` point is in, the trace follows the center of the cursor cube.
+fn trace_voxel(point: Vec3) -> IVec3 {
+    point.floor().as_ivec3()
+}
+
+/// The selected region, from where the select drag started to where the cursor is now.
+#[derive(Resource, Default)]
+pub(crate) struct Selection {
+    pub(crate) region: Option<Region>,
+    dragging: bool,
+}
+
+impl Selection {
+    /// Whether the region is being dragged out, the camera holds still meanwhile.
+    pub(crate) fn dragging(&self) -> bool {
+        self.dragging
+    }
+}
+
+/// The translucent box showing the selection.
+#[derive(Component)]
+pub(crate) struct SelectionBox;
+
+pub(crate) fn setup_selection_box(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        SelectionBox,
+        Mesh3d(meshes.add(Cuboid::from_length(1.0))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgba_u8(255, 220, 90, 64),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        })),
+        Transform::default(),
+        Visibility::Hidden,
+    ));
+}
+
+pub(crate) fn drag_selection(
+    actions: Res<Actions>,
+    mut trace: ResMut<VoxelTrace>,
+    mut selection: ResMut<Selection>,
+) {
+    if actions.just_pressed(Action::Select) {
+        trace.start = Some(trace.end);
+        selection.dragging = true;
+    }
+    if selection.dragging {
+        if let Some(start) = trace.start {
+            selection.region = Some(Region::from_corners(
+                trace_voxel(start),
+                trace_voxel(trace.end),
+            ));
+        }
+        if !actions.pressed(Action::Select) {
+            selection.dragging = false;
+            trace.start = None;
+        }
+    }
+}
+
+pub(crate) fn update_selection_box(
+    selection: Res<Selection>,
+    mut selection_box: Query<(&mut Transform, &mut Visibility), With<SelectionBox>>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    let (mut transform, mut visibility) = selection_box.single_mut();
+    let Some(region) = selection.region else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let size = region.size().as_vec3();
+    // Slightly larger than the region so the faces don't fight with the voxels
+    transform.translation = region.min.as_vec3() + size / 2.0;
+    transform.scale = size + Vec3::splat(0.02);
+    *visibility = Visibility::Visible;
+}
+
+/// Fill, hollow out or clear the selected region, or drop the selection.
+pub(crate) fn edit_selection(
+    actions: Res<Actions>,
+    mut selection: ResMut<Selection>,
+    mut editor: WorldEditor,
+    cursor_cube: Query<&CursorCube>,
+) {
+    let Some(region) = selection.region else {
+        return;
+    };
+    let editing = [Action::Fill, Action::Hollow, Action::ClearSelection]
+        .into_iter()
+        .any(|action| actions.just_pressed(action));
+    if editing && region.volume() > MAX_FILL {
+        warn!(
+            "The selection is {} voxels, fill at most {}",
+            region.volume(),
+            MAX_FILL
+        );
+        return;
+    }
+    let material = WorldVoxel::Solid(cursor_cube.single().voxel_mat);
+    if actions.just_pressed(Action::Fill) {
+        for pos in region.voxels() {
+            editor.set_voxel(pos, material);
+        }
+    } else if actions.just_pressed(Action::Hollow) {
+        for pos in region.voxels() {
+            let voxel = if region.on_boundary(pos) {
+                material
+            } else {
+                WorldVoxel::Air
+            };
+            editor.set_voxel(pos, voxel);
+        }
+    } else if actions.just_pressed(Action::ClearSelection) {
+        for pos in region.voxels() {
+            editor.set_voxel(pos, WorldVoxel::Air);
+        }
+    } else if actions.just_pressed(Action::DropSelection) {
+        selection.region = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_from_any_corners() {
+        let sut = Region::from_corners(IVec3::new(3, -1, 0), IVec3::new(1, 2, -2));
+        assert_eq!(sut.min, IVec3::new(1, -1, -2));
+        assert_eq!(sut.max, IVec3::new(3, 2, 0));
+        assert_eq!(sut.size(), IVec3::new(3, 4, 3));
+        assert_eq!(sut.voxels().count(), 36);
+    }
+
+    #[test]
+    fn boundary_of_region() {
+        let sut = Region::from_corners(IVec3::ZERO, IVec3::splat(2));
+        assert!(sut.on_boundary(IVec3::ZERO));
+        assert!(sut.on_boundary(IVec3::new(1, 1, 2)));
+        assert!(!sut.on_boundary(IVec3::ONE));
+        assert!(!sut.on_boundary(IVec3::new(1, 1, 3)));
+        assert_eq!(sut.voxels().filter(|pos| sut.on_boundary(*pos)).count(), 26);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::cursor::CursorCube;
+use crate::editing::WorldEditor;
+use crate::input::{Action, Actions};
+use crate::selection::{Region, MAX_FILL};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_voxel_world::prelude::*;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+const MAX_RADIUS: i32 = 32;
+const MAX_HEIGHT: i32 = 64;
+/// Previews of more voxels than this only draw the voxels on the surface.
+const MAX_PREVIEW_VOXELS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shape {
+    /// Line between two picks.
+    Line,
+    /// Sphere around the cursor.
+    Sphere,
+    /// Upright cylinder standing on the cursor.
+    Cylinder,
+    /// Wall between two picks, `radius` voxels thick.
+    Wall,
+}
+
+impl Shape {
+    /// Whether the shape is placed with a second pick after the anchor.
+    fn needs_anchor(&self) -> bool {
+        matches!(self, Shape::Line | Shape::Wall)
+    }
+}
+
+/// The active shape brush. When set, left clicks place the shape instead of a single block.
+#[derive(Resource, Debug)]
+pub(crate) struct ShapeTool {
+    pub(crate) shape: Option<Shape>,
+    pub(crate) radius: i32,
+    pub(crate) height: i32,
+    pub(crate) hollow: bool,
+    /// First pick of a two pick shape.
+    anchor: Option<IVec3>,
+}
+
+impl Default for ShapeTool {
+    fn default() -> Self {
+        Self {
+            shape: None,
+            radius: 3,
+            height: 4,
+            hollow: false,
+            anchor: None,
+        }
+    }
+}
+
+impl ShapeTool {
+    /// The voxels the shape covers with the cursor at `cursor`, `None` while waiting for the
+    /// anchor of a two pick shape.
+    fn voxels(&self, cursor: IVec3) -> Option<Vec<IVec3>> {
+        let shape = self.shape?;
+        let voxels = match (shape, self.anchor) {
+            (Shape::Line, Some(anchor)) => line(anchor, cursor),
+            (Shape::Wall, Some(anchor)) => wall(anchor, cursor, self.radius, self.height),
+            (Shape::Sphere, _) => sphere(cursor, self.radius, self.hollow),
+            (Shape::Cylinder, _) => cylinder(cursor, self.radius, self.height, self.hollow),
+            _ => return None,
+        };
+        Some(voxels)
+    }
+}
+
+/// Voxels along a straight line between `a` and `b`, both included.
+pub(crate) fn line(a: IVec3, b: IVec3) -> Vec<IVec3> {
+    let delta = b - a;
+    let steps = delta.abs().max_element();
+    if steps == 0 {
+        return vec![a];
+    }
+    (0..=steps)
+        .map(|step| {
+            let t = step as f32 / steps as f32;
+            (a.as_vec3() + delta.as_vec3() * t).round().as_ivec3()
+        })
+        .collect()
+}
+
+/// The smallest box around the voxels, `None` without voxels.
+fn bounds(voxels: &[IVec3]) -> Option<Region> {
+    let first = *voxels.first()?;
+    let (min, max) = voxels.iter().fold((first, first), |(min, max), pos| {
+        (min.min(*pos), max.max(*pos))
+    });
+    Some(Region { min, max })
+}
+
+/// The voxels a preview draws, all of them for small previews and the surface of larger ones.
+pub(crate) fn ghost(voxels: Vec<IVec3>) -> Vec<IVec3> {
+    if voxels.len() <= MAX_PREVIEW_VOXELS {
+        voxels
+    } else {
+        shell(voxels)
+    }
+}
+
+/// Keep only the voxels of a solid that have a neighbour outside of it.
+fn shell(voxels: Vec<IVec3>) -> Vec<IVec3> {
+    let solid: HashSet<IVec3> = voxels.iter().copied().collect();
+    voxels
+        .into_iter()
+        .filter(|pos| {
+            NEIGHBOURS
+                .iter()
+                .any(|offset| !solid.contains(&(*pos + *offset)))
+        })
+        .collect()
+}
+
+pub(crate) fn sphere(center: IVec3, radius: i32, hollow: bool) -> Vec<IVec3> {
+    let reach = IVec3::splat(radius);
+    let limit = radius * radius + radius;
+    let voxels: Vec<IVec3> = (-reach.x..=reach.x)
+        .flat_map(|x| {
+            (-reach.y..=reach.y)
+                .flat_map(move |y| (-reach.z..=reach.z).map(move |z| IVec3::new(x, y, z)))
+        })
+        .filter(|offset| offset.length_squared() <= limit)
+        .map(|offset| center + offset)
+        .collect();
+    if hollow {
+        shell(voxels)
+    } else {
+        voxels
+    }
+}
+
+/// Upright cylinder with its bottom layer at `base`.
+pub(crate) fn cylinder(base: IVec3, radius: i32, height: i32, hollow: bool) -> Vec<IVec3> {
+    let limit = radius * radius + radius;
+    let voxels: Vec<IVec3> = (0..height)
+        .flat_map(|y| {
+            (-radius..=radius)
+                .flat_map(move |x| (-radius..=radius).map(move |z| IVec3::new(x, y, z)))
+        })
+        .filter(|offset| offset.x * offset.x + offset.z * offset.z <= limit)
+        .map(|offset| base + offset)
+        .collect();
+    if hollow {
+        shell(voxels)
+    } else {
+        voxels
+    }
+}
+
+/// Wall standing on the line from `a` to `b` at the height of `a`, thickened across the line.
+pub(crate) fn wall(a: IVec3, b: IVec3, thickness: i32, height: i32) -> Vec<IVec3> {
+    let b = IVec3::new(b.x, a.y, b.z);
+    let delta = b - a;
+    // Thicken along the axis the wall runs least along
+    let across = if delta.x.abs() >= delta.z.abs() {
+        IVec3::Z
+    } else {
+        IVec3::X
+    };
+    let from = -(thickness - 1) / 2;
+    let to = thickness / 2;
+    let voxels: HashSet<IVec3> = line(a, b)
+        .into_iter()
+        .flat_map(|pos| (from..=to).map(move |offset| pos + across * offset))
+        .flat_map(|pos| (0..height).map(move |y| pos + IVec3::Y * y))
+        .collect();
+    voxels.into_iter().collect()
+}
+
+/// Cycle the shape, toggle hollow and change the radius and height.
+pub(crate) fn shape_tool_keys(actions: Res<Actions>, mut tool: ResMut<ShapeTool>) {
+    if actions.just_pressed(Action::CycleShape) {
+        tool.shape = match tool.shape {
+            None => Some(Shape::Line),
+            Some(Shape::Line) => Some(Shape::Sphere),
+            Some(Shape::Sphere) => Some(Shape::Cylinder),
+            Some(Shape::Cylinder) => Some(Shape::Wall),
+            Some(Shape::Wall) => None,
+        };
+        tool.anchor = None;
+        info!("Shape tool {:?}", tool.shape);
+    }
+    if actions.just_pressed(Action::ToggleHollow) {
+        tool.hollow = !tool.hollow;
+    }
+    if actions.just_pressed(Action::RadiusDown) {
+        tool.radius = (tool.radius - 1).max(1);
+    }
+    if actions.just_pressed(Action::RadiusUp) {
+        tool.radius = (tool.radius + 1).min(MAX_RADIUS);
+    }
+    if actions.just_pressed(Action::HeightDown) {
+        tool.height = (tool.height - 1).max(1);
+    }
+    if actions.just_pressed(Action::HeightUp) {
+        tool.height = (tool.height + 1).min(MAX_HEIGHT);
+    }
+}
+
+/// Placing sets the anchor of two pick shapes, or places the shape.
+pub(crate) fn place_shape(
+    mut actions: ResMut<Actions>,
+    mut tool: ResMut<ShapeTool>,
+    mut editor: WorldEditor,
+    cursor_cube: Query<&CursorCube>,
+) {
+    let Some(shape) = tool.shape else {
+        return;
+    };
+    if !actions.just_pressed(Action::Place) {
+        return;
+    }
+    // The cursor doesn't place a block of its own, even when the shape isn't placed
+    actions.consume(Action::Place);
+    let cursor_cube = cursor_cube.single();
+    if shape.needs_anchor() && tool.anchor.is_none() {
+        tool.anchor = Some(cursor_cube.voxel_pos);
+        return;
+    }
+    let Some(voxels) = tool.voxels(cursor_cube.voxel_pos) else {
+        return;
+    };
+    // The anchor is kept, so a closer second pick can still place a two pick shape
+    if voxels.len() as i64 > MAX_FILL {
+        warn!(
+            "The shape is {} voxels, place at most {}",
+            voxels.len(),
+            MAX_FILL
+        );
+        return;
+    }
+    for pos in voxels {
+        editor.set_voxel(pos, WorldVoxel::Solid(cursor_cube.voxel_mat));
+    }
+    tool.anchor = None;
+}
+
+/// Ghost of the shape that a click would place, or a red box around a shape too large to place.
+/// The shape is only built again when the cursor or the tool changes.
+pub(crate) fn draw_shape_preview(
+    tool: Res<ShapeTool>,
+    cursor_cube: Query<&CursorCube>,
+    mut preview: Local<Option<(IVec3, Preview)>>,
+    mut gizmos: Gizmos,
+) {
+    let cursor = cursor_cube.single().voxel_pos;
+    if let Some(anchor) = tool.anchor {
+        gizmos.cuboid(
+            Transform::from_translation(anchor.as_vec3() + Vec3::splat(0.5)),
+            Color::srgb(1.0, 0.4, 0.2),
+        );
+    }
+    if tool.is_changed() || preview.as_ref().is_none_or(|(at, _)| *at != cursor) {
+        let shape = match tool.voxels(cursor) {
+            Some(voxels) if voxels.len() as i64 > MAX_FILL => {
+                bounds(&voxels).map_or(Preview::Nothing, Preview::TooLarge)
+            }
+            Some(voxels) => Preview::Ghost(ghost(voxels)),
+            None => Preview::Nothing,
+        };
+        *preview = Some((cursor, shape));
+    }
+    match preview.as_ref().map(|(_, shape)| shape) {
+        Some(Preview::Ghost(voxels)) => {
+            for pos in voxels {
+                gizmos.cuboid(
+                    Transform::from_translation(pos.as_vec3() + Vec3::splat(0.5)),
+                    Color::srgba(0.5, 0.8, 1.0, 0.5),
+                );
+            }
+        }
+        Some(Preview::TooLarge(region)) => {
+            let size = region.size().as_vec3();
+            gizmos.cuboid(
+                Transform::from_translation(region.min.as_vec3() + size / 2.0).with_scale(size),
+                Color::srgb(1.0, 0.2, 0.2),
+            );
+        }
+        Some(Preview::Nothing) | None => {}
+    }
+}
+
+/// What the shape preview draws.
+pub(crate) enum Preview {
+    Nothing,
+    Ghost(Vec<IVec3>),
+    /// Bounds of a shape larger than `MAX_FILL`, which can't be placed.
+    TooLarge(Region),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_of_sphere() {
+        let sut = bounds(&sphere(IVec3::new(1, 2, 3), 2, true)).unwrap();
+        assert_eq!(sut.min, IVec3::new(-1, 0, 1));
+        assert_eq!(sut.max, IVec3::new(3, 4, 5));
+        assert_eq!(bounds(&[]), None);
+    }
+
+    #[test]
+    fn ghost_of_large_shape_is_its_surface() {
+        let small = sphere(IVec3::ZERO, 4, false);
+        assert_eq!(ghost(small.clone()), small);
+        let large = sphere(IVec3::ZERO, 12, false);
+        let sut = ghost(large.clone());
+        assert!(sut.len() < large.len());
+        assert!(!sut.contains(&IVec3::ZERO));
+        assert!(sut.contains(&IVec3::new(12, 0, 0)));
+    }
+
+    #[test]
+    fn line_is_connected() {
+        let sut = line(IVec3::ZERO, IVec3::new(6, 3, -2));
+        assert_eq!(sut.len(), 7);
+        assert_eq!(sut.first(), Some(&IVec3::ZERO));
+        assert_eq!(sut.last(), Some(&IVec3::new(6, 3, -2)));
+        for pair in sut.windows(2) {
+            assert!((pair[1] - pair[0]).abs().max_element() <= 1);
+        }
+    }
+
+    #[test]
+    fn hollow_sphere_is_surface_of_solid() {
+        let solid = sphere(IVec3::ZERO, 4, false);
+        let hollow = sphere(IVec3::ZERO, 4, true);
+        assert!(solid.contains(&IVec3::ZERO));
+        assert!(!hollow.contains(&IVec3::ZERO));
+        assert!(hollow.contains(&IVec3::new(4, 0, 0)));
+        assert!(hollow.iter().all(|pos| solid.contains(pos)));
+    }
+
+    #[test]
+    fn cylinder_has_height() {
+        let sut = cylinder(IVec3::ZERO, 2, 5, false);
+        assert_eq!(sut.iter().map(|pos| pos.y).max(), Some(4));
+        assert_eq!(sut.iter().filter(|pos| pos.y == 0).count(), 21);
+    }
+
+    #[test]
+    fn wall_is_thick_across() {
+        let sut = wall(IVec3::ZERO, IVec3::new(5, 7, 1), 2, 3);
+        assert_eq!(sut.len(), 6 * 2 * 3);
+        assert!(sut.iter().all(|pos| (0..3).contains(&pos.y)));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::blocks::is_liquid;
+use crate::chunk_mesh;
+use crate::editing::VoxelChanged;
+use crate::input;
+use crate::lighting::{self, BlockLight};
+use crate::map::{Map, Size};
+use crate::texture_pack::{self, SharedTexturePack, TexturePack, TexturePackApplied, TexturePacks};
+use crate::textures::BlockTexture;
+use bevy::pbr::{
+    CascadeShadowConfigBuilder, ExtendedMaterial, MaterialExtension, MaterialExtensionKey,
+    MaterialExtensionPipeline,
+};
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderDefVal, ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy_voxel_world::prelude::*;
+use bevy_voxel_world::rendering::{vertex_layout, VOXEL_TEXTURE_SHADER_HANDLE};
+use std::f32::consts::PI;
+use std::sync::{Arc, RwLock};
+
+/// Config of the terrain world, its voxels are generated from a map.
+#[derive(Resource, Clone)]
+pub struct MyMainWorld {
+    pub(crate) static_map: Arc<Map>,
+    pub(crate) texture_pack: SharedTexturePack,
+}
+
+impl Default for MyMainWorld {
+    fn default() -> Self {
+        warn!("MyMainWorld::default() called");
+        Self {
+            static_map: Arc::new(Map::noise_map(Size::new(20, 20))),
+            texture_pack: Arc::new(RwLock::new(TexturePack::default())),
+        }
+    }
+}
+
+impl MyMainWorld {
+    pub fn new(static_map: Arc<Map>) -> Self {
+        warn!("MyMainWorld::new() called");
+        Self {
+            static_map,
+            texture_pack: Arc::new(RwLock::new(TexturePack::default())),
+        }
+    }
+}
+
+impl VoxelWorldConfig for MyMainWorld {
+    type MaterialIndex = BlockTexture;
+
+    fn texture_index_mapper(&self) -> Arc<dyn Fn(Self::MaterialIndex) -> [u32; 3] + Send + Sync> {
+        // Snapshot the active pack, so a chunk is meshed with one pack even if it is switched
+        let pack = self.texture_pack.read().unwrap().clone();
+        Arc::new(move |vox_mat| vox_mat.encode_tiles(pack.index(vox_mat)))
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+        let map = self.static_map.clone();
+        Box::new(move |_chunk_pos| get_voxel_fn(map.clone()))
+    }
+
+    fn init_custom_materials(&self) -> bool {
+        // The block material is created by `apply_texture_pack` once the atlas has loaded
+        false
+    }
+}
+fn get_voxel_fn(world_map: Arc<Map>) -> VoxelLookupFn<BlockTexture> {
+    Box::new(move |pos: IVec3| terrain_voxel(&world_map, pos))
+}
+
+/// The generated terrain voxel at a position, before any edits.
+pub(crate) fn terrain_voxel(world_map: &Map, pos: IVec3) -> WorldVoxel<BlockTexture> {
+    // Liquids are rendered by the water world, the terrain only holds the ground below them
+    match world_map.voxel_at(pos) {
+        voxel if is_liquid(voxel) => WorldVoxel::Air,
+        voxel => voxel,
+    }
+}
+
+/// Like the default `bevy_voxel_world` material, but owned by us so the atlas can be swapped, and
+/// with a fragment shader that supports rotated and cut out tiles.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub(crate) struct BlockMaterialExtension {
+    #[texture(100, dimension = "2d_array")]
+    #[sampler(101)]
+    pub(crate) voxels_texture: Handle<Image>,
+}
+
+pub(crate) type BlockMaterial = ExtendedMaterial<StandardMaterial, BlockMaterialExtension>;
+
+impl MaterialExtension for BlockMaterialExtension {
+    fn vertex_shader() -> ShaderRef {
+        VOXEL_TEXTURE_SHADER_HANDLE.into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "block_material.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&ShaderDefVal::Bool("PREPASS_PIPELINE".into(), true))
+        {
+            return Ok(());
+        }
+
+        let vertex_layout = layout.0.get_layout(&vertex_layout())?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        Ok(())
+    }
+}
+
+/// How the terrain is lit.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSettings {
+    /// Illuminance of the sun in lux.
+    pub illuminance: f32,
+    pub shadows: bool,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            illuminance: light_consts::lux::OVERCAST_DAY,
+            shadows: true,
+        }
+    }
+}
+
+/// The terrain world of a map with its block material, texture packs, block light and sun.
+pub struct TerrainPlugin {
+    pub map: Arc<Map>,
+    pub settings: TerrainSettings,
+}
+
+impl TerrainPlugin {
+    pub fn new(map: Arc<Map>) -> Self {
+        Self {
+            map,
+            settings: TerrainSettings::default(),
+        }
+    }
+}
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        input::add_actions(app);
+        app.insert_resource(self.settings)
+            .add_plugins(MaterialPlugin::<BlockMaterial>::default())
+            .add_plugins(
+                VoxelWorldPlugin::with_config(MyMainWorld::new(self.map.clone())).with_material(
+                    BlockMaterial {
+                        base: StandardMaterial::default(),
+                        extension: BlockMaterialExtension {
+                            voxels_texture: Handle::default(),
+                        },
+                    },
+                ),
+            )
+            .init_resource::<TexturePacks>()
+            .init_resource::<BlockLight>()
+            .init_resource::<chunk_mesh::BakedMeshes>()
+            .add_event::<TexturePackApplied>()
+            .add_event::<VoxelChanged>()
+            .add_systems(Startup, (spawn_sun, texture_pack::setup_texture_packs))
+            .add_systems(
+                Update,
+                (
+                    (
+                        texture_pack::switch_texture_pack,
+                        texture_pack::apply_texture_pack,
+                    )
+                        .chain(),
+                    lighting::update_block_light,
+                ),
+            )
+            // After `bevy_voxel_world` has given chunks their new meshes in `Update`
+            .add_systems(PostUpdate, chunk_mesh::bake_chunk_meshes);
+    }
+}
+
+/// Directional 'sun' light.
+fn spawn_sun(mut commands: Commands, settings: Res<TerrainSettings>) {
+    commands.spawn((
+        DirectionalLight {
+            illuminance: settings.illuminance,
+            shadows_enabled: settings.shadows,
+            ..default()
+        },
+        Transform {
+            translation: Vec3::new(0.0, 2.0, 0.0),
+            rotation: Quat::from_rotation_x(-PI / 4.),
+            ..default()
+        },
+        // The default cascade config is designed to handle large scenes.
+        // As this example has a much smaller world, we can tighten the shadow
+        // bounds for better visual quality.
+        CascadeShadowConfigBuilder {
+            first_cascade_far_bound: 4.0,
+            maximum_distance: 10.0,
+            ..default()
+        }
+        .build(),
+    ));
+}
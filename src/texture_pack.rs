@@ -0,0 +1,363 @@
+use crate::console::files_in;
+use crate::input::{Action, Actions};
+use crate::terrain::{BlockMaterial, BlockMaterialExtension};
+use crate::textures::BlockTexture;
+use crate::MyMainWorld;
+use bevy::asset::LoadState;
+use bevy::pbr::ExtendedMaterial;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_voxel_world::prelude::*;
+use bevy_voxel_world::rendering::VoxelWorldMaterialHandle;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+pub(crate) const TEXTURE_PACK_DIR: &str = "assets/texture_packs";
+const TEXTURE_PACK_EXTENSION: &str = "pack";
+
+/// An atlas image plus the tiles each block uses from it.
+///
+/// Pack files live in `assets/texture_packs/<name>.pack` and look like:
+///
+/// ```text
+/// # comment
+/// atlas voxel_textures_all.png 85
+/// GrassBrick 23 9 9
+/// ```
+///
+/// where the atlas line gives the image (relative to `assets/`) and its number of stacked tiles,
+/// and every block line gives the top, side and bottom tile.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TexturePack {
+    pub(crate) name: String,
+    pub(crate) atlas: String,
+    pub(crate) layers: u32,
+    tiles: HashMap<BlockTexture, [u32; 3]>,
+}
+
+/// The built-in mapping of the kenney atlas, used when `kenney.pack` can't be loaded.
+impl Default for TexturePack {
+    fn default() -> Self {
+        let (atlas, layers) = BlockTexture::get_texture();
+        Self {
+            name: "kenney".into(),
+            atlas,
+            layers,
+            tiles: BlockTexture::ALL
+                .into_iter()
+                .map(|block| (block, block.index_mapper()))
+                .collect(),
+        }
+    }
+}
+
+impl TexturePack {
+    pub(crate) fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut atlas = None;
+        let mut tiles = HashMap::new();
+
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: String| format!("{}:{}: {}", name, line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["atlas", path, layers] => {
+                    let layers = layers
+                        .parse::<u32>()
+                        .map_err(|_| error(format!("invalid layer count '{}'", layers)))?;
+                    atlas = Some((path.to_string(), layers));
+                }
+                [block, top, side, bottom] => {
+                    let block = block.parse::<BlockTexture>().map_err(error)?;
+                    let mut indexes = [0; 3];
+                    for (index, word) in indexes.iter_mut().zip([top, side, bottom]) {
+                        *index = word
+                            .parse()
+                            .map_err(|_| error(format!("invalid tile index '{}'", word)))?;
+                    }
+                    tiles.insert(block, indexes);
+                }
+                _ => return Err(error(format!("can't parse '{}'", line))),
+            }
+        }
+
+        let (atlas, layers) = atlas.ok_or_else(|| format!("{}: missing atlas line", name))?;
+        if let Some((block, _)) = tiles
+            .iter()
+            .find(|(_, indexes)| indexes.iter().any(|index| *index >= layers))
+        {
+            return Err(format!(
+                "{}: tile for {:?} is outside the {} atlas layers",
+                name, block, layers
+            ));
+        }
+
+        Ok(Self {
+            name: name.into(),
+            atlas,
+            layers,
+            tiles,
+        })
+    }
+
+    pub(crate) fn load(name: &str) -> Result<Self, String> {
+        let path = Path::new(TEXTURE_PACK_DIR).join(format!("{}.{}", name, TEXTURE_PACK_EXTENSION));
+        let source = fs::read_to_string(&path)
+            .map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        let pack = Self::parse(name, &source)?;
+        let missing = pack.missing_blocks();
+        if !missing.is_empty() {
+            warn!(
+                "Texture pack {} has no tiles for {:?}, they use the first tile",
+                name, missing
+            );
+        }
+        Ok(pack)
+    }
+
+    /// Blocks the pack has no tiles for.
+    pub(crate) fn missing_blocks(&self) -> Vec<BlockTexture> {
+        BlockTexture::ALL
+            .into_iter()
+            .filter(|block| !self.tiles.contains_key(block))
+            .collect()
+    }
+
+    /// Top, side and bottom tiles for the block, blocks missing from the pack use the first tile.
+    pub(crate) fn index(&self, block: BlockTexture) -> [u32; 3] {
+        self.tiles.get(&block).copied().unwrap_or_default()
+    }
+
+    /// Names of all pack files found in the texture pack directory, sorted.
+    pub(crate) fn available() -> Vec<String> {
+        files_in(TEXTURE_PACK_DIR, TEXTURE_PACK_EXTENSION)
+    }
+}
+
+/// The pack used by `MyMainWorld`, shared with the texture index mapper of the meshing tasks.
+pub(crate) type SharedTexturePack = Arc<RwLock<TexturePack>>;
+
+#[derive(Resource, Default)]
+pub(crate) struct TexturePacks {
+    names: Vec<String>,
+    active: usize,
+    /// The active pack and its atlas while the atlas is still loading. Chunks keep being meshed
+    /// with the old pack until the atlas can be used.
+    loading: Option<(TexturePack, Handle<Image>)>,
+}
+
+/// Load the pack file of the pack the world starts with, keeping its built-in mapping when the
+/// file can't be loaded.
+pub(crate) fn setup_texture_packs(
+    mut packs: ResMut<TexturePacks>,
+    world: Res<MyMainWorld>,
+    asset_server: Res<AssetServer>,
+) {
+    packs.names = TexturePack::available();
+    let builtin = world.texture_pack.read().unwrap().clone();
+    packs.active = packs
+        .names
+        .iter()
+        .position(|name| *name == builtin.name)
+        .unwrap_or_default();
+    let pack = TexturePack::load(&builtin.name).unwrap_or_else(|err| {
+        warn!("{}, using the built-in {} tiles", err, builtin.name);
+        builtin
+    });
+    let atlas = asset_server.load(pack.atlas.clone());
+    packs.loading = Some((pack, atlas));
+}
+
+/// Switch to the next pack, or reload the active one from disk.
+pub(crate) fn switch_texture_pack(
+    actions: Res<Actions>,
+    mut packs: ResMut<TexturePacks>,
+    asset_server: Res<AssetServer>,
+) {
+    if packs.names.is_empty() {
+        return;
+    }
+    if actions.just_pressed(Action::NextTexturePack) {
+        packs.active = (packs.active + 1) % packs.names.len();
+    } else if !actions.just_pressed(Action::ReloadTexturePack) {
+        return;
+    }
+
+    let name = &packs.names[packs.active];
+    match TexturePack::load(name) {
+        Ok(pack) => {
+            info!("Switching to texture pack '{}'", pack.name);
+            let atlas = asset_server.load(pack.atlas.clone());
+            packs.loading = Some((pack, atlas));
+        }
+        Err(err) => error!("Failed to load texture pack: {}", err),
+    }
+}
+
+/// The atlas of a texture pack has been loaded and is now used by the block material.
+#[derive(Event)]
+pub(crate) struct TexturePackApplied {
+    pub(crate) atlas: Handle<Image>,
+}
+
+/// Once the atlas of the active pack is loaded, point the block material at it, hand the tiles
+/// of the pack to the world and respawn all terrain chunks so they are meshed with them.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_texture_pack(
+    mut commands: Commands,
+    mut packs: ResMut<TexturePacks>,
+    world: Res<MyMainWorld>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BlockMaterial>>,
+    material_handle: Option<Res<VoxelWorldMaterialHandle<BlockMaterial>>>,
+    chunks: Query<Entity, With<Chunk<MyMainWorld>>>,
+    mut applied: EventWriter<TexturePackApplied>,
+) {
+    let Some((_, handle)) = &packs.loading else {
+        return;
+    };
+    match asset_server.get_load_state(handle) {
+        Some(LoadState::Loaded) => {}
+        Some(LoadState::Failed(err)) => {
+            error!("Failed to load texture pack atlas: {}", err);
+            packs.loading = None;
+            return;
+        }
+        _ => return,
+    }
+    let Some((pack, handle)) = packs.loading.take() else {
+        return;
+    };
+
+    let Some(image) = images.get_mut(&handle) else {
+        error!("The texture pack atlas was removed before it could be used");
+        return;
+    };
+    // Switching back to a pack reuses the atlas image that was already turned into an array
+    let size = image.texture_descriptor.size;
+    if let Err(err) = atlas_layers_fit(size.height, size.depth_or_array_layers, pack.layers) {
+        error!("Keeping the old texture pack, {}: {}", pack.atlas, err);
+        return;
+    }
+    if size.depth_or_array_layers == 1 {
+        image.reinterpret_stacked_2d_as_array(pack.layers);
+    }
+    *world.texture_pack.write().unwrap() = pack;
+    applied.send(TexturePackApplied {
+        atlas: handle.clone(),
+    });
+
+    match material_handle {
+        Some(material_handle) => {
+            if let Some(material) = materials.get_mut(&material_handle.handle) {
+                material.extension.voxels_texture = handle;
+            }
+            for chunk in chunks.iter() {
+                commands.entity(chunk).try_insert(NeedsDespawn);
+            }
+        }
+        None => {
+            let handle = materials.add(ExtendedMaterial {
+                base: StandardMaterial {
+                    alpha_mode: AlphaMode::Mask(0.5),
+                    reflectance: 0.05,
+                    metallic: 0.05,
+                    perceptual_roughness: 0.95,
+                    ..default()
+                },
+                extension: BlockMaterialExtension {
+                    voxels_texture: handle,
+                },
+            });
+            commands.insert_resource(VoxelWorldMaterialHandle { handle });
+        }
+    }
+}
+
+/// Whether an atlas image `height` pixels high with `depth` array layers can hold `layers`
+/// tiles, stacked images are turned into an array of that many layers.
+fn atlas_layers_fit(height: u32, depth: u32, layers: u32) -> Result<(), String> {
+    match depth {
+        _ if layers == 0 => Err("the pack has no atlas layers".into()),
+        1 if !height.is_multiple_of(layers) => Err(format!(
+            "an image {} pixels high can't be split into {} layers",
+            height, layers
+        )),
+        1 => Ok(()),
+        _ if depth != layers => Err(format!(
+            "the image has {} layers, the pack {}",
+            depth, layers
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pack() {
+        let source = "# test pack\natlas test.png 4\nGrassBrick 3 2 2 # grass on brick\n";
+        let sut = TexturePack::parse("test", source).unwrap();
+        assert_eq!(sut.atlas, "test.png");
+        assert_eq!(sut.layers, 4);
+        assert_eq!(sut.index(BlockTexture::GrassBrick), [3, 2, 2]);
+        assert_eq!(sut.index(BlockTexture::StoneBrick), [0, 0, 0]);
+    }
+
+    #[test]
+    fn bundled_packs_have_every_block() {
+        for name in ["brick", "kenney"] {
+            let sut = TexturePack::load(name).unwrap();
+            assert_eq!(sut.missing_blocks(), vec![], "{}", name);
+        }
+    }
+
+    #[test]
+    fn kenney_pack_matches_builtin_tiles() {
+        let sut = TexturePack::load("kenney").unwrap();
+        assert_eq!(sut, TexturePack::default());
+    }
+
+    #[test]
+    fn atlas_must_split_into_layers() {
+        assert_eq!(atlas_layers_fit(64, 1, 4), Ok(()));
+        assert_eq!(atlas_layers_fit(256, 4, 4), Ok(()));
+        assert_eq!(
+            atlas_layers_fit(65, 1, 4),
+            Err("an image 65 pixels high can't be split into 4 layers".into())
+        );
+        assert_eq!(
+            atlas_layers_fit(64, 1, 0),
+            Err("the pack has no atlas layers".into())
+        );
+        assert_eq!(
+            atlas_layers_fit(256, 4, 85),
+            Err("the image has 4 layers, the pack 85".into())
+        );
+    }
+
+    #[test]
+    fn parse_pack_without_atlas() {
+        let sut = TexturePack::parse("test", "GrassBrick 3 2 2\n");
+        assert!(sut.is_err());
+    }
+
+    #[test]
+    fn parse_pack_tile_outside_atlas() {
+        let sut = TexturePack::parse("test", "atlas test.png 4\nGrassBrick 4 2 2\n");
+        assert!(sut.is_err());
+    }
+
+    #[test]
+    fn parse_pack_unknown_block() {
+        let sut = TexturePack::parse("test", "atlas test.png 4\nCheeseBrick 1 2 2\n");
+        assert_eq!(sut, Err("test:2: unknown block 'CheeseBrick'".into()));
+    }
+}
@@ -1,3 +1,4 @@
+use std::str::FromStr;
 
 // 00 - brick_grey.png
 // 01 - brick_red.png
@@ -85,7 +86,6 @@
 // 83 - wood.png
 // 84 - wood_red.png
 
-
 // Using enum for material index allows for more than u8::MAX number of materials.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
 pub enum BlockTexture {
@@ -99,9 +99,77 @@ pub enum BlockTexture {
     RockBrick,
     WaterBrick,
     FullBrick,
+    FenceStone,
+    FenceStonePost,
+    FenceWood,
+    FenceWoodPost,
+    GlassFrame,
+    GlassPane,
+    TrackNorthSouth,
+    TrackEastWest,
+    TrackNorthEast,
+    TrackSouthEast,
+    TrackSouthWest,
+    TrackNorthWest,
+    Leaves,
+    Lava,
+    RubyOre,
+    EmeraldOre,
+    DiamondOre,
+}
+
+/// Tile indexes carry a number of quarter turns for the tile above this bit, the layer is below it.
+pub(crate) const TILE_ROTATION_SHIFT: u32 = 16;
+/// Above the rotation, tiles carry a tint level, 0 for untinted tiles.
+pub(crate) const TILE_TINT_SHIFT: u32 = 18;
+const TILE_TINT_MASK: u32 = 0b111 << TILE_TINT_SHIFT;
+/// Tint level of tinted tiles before the tint of their column is known.
+const NEUTRAL_TINT: u32 = 4;
+
+/// Tint level 1 (dry) to 7 (lush) for a column with the given moisture.
+pub(crate) fn tint_level(moisture: u8) -> u32 {
+    1 + moisture as u32 * 7 / 256
+}
+
+/// Replace the tint of a tinted tile, untinted tiles are left as they are.
+pub(crate) fn retint_tile(tile: u32, tint: u32) -> u32 {
+    if tile & TILE_TINT_MASK == 0 {
+        tile
+    } else {
+        (tile & !TILE_TINT_MASK) | tint << TILE_TINT_SHIFT
+    }
 }
 
 impl BlockTexture {
+    pub const ALL: [BlockTexture; 26] = [
+        BlockTexture::GrassBrick,
+        BlockTexture::SnowyBrick,
+        BlockTexture::DirtBrick,
+        BlockTexture::SandBrick,
+        BlockTexture::GravelBrick,
+        BlockTexture::StoneBrick,
+        BlockTexture::RockBrick,
+        BlockTexture::WaterBrick,
+        BlockTexture::FullBrick,
+        BlockTexture::FenceStone,
+        BlockTexture::FenceStonePost,
+        BlockTexture::FenceWood,
+        BlockTexture::FenceWoodPost,
+        BlockTexture::GlassFrame,
+        BlockTexture::GlassPane,
+        BlockTexture::TrackNorthSouth,
+        BlockTexture::TrackEastWest,
+        BlockTexture::TrackNorthEast,
+        BlockTexture::TrackSouthEast,
+        BlockTexture::TrackSouthWest,
+        BlockTexture::TrackNorthWest,
+        BlockTexture::Leaves,
+        BlockTexture::Lava,
+        BlockTexture::RubyOre,
+        BlockTexture::EmeraldOre,
+        BlockTexture::DiamondOre,
+    ];
+
     pub(crate) fn index_mapper(&self) -> [u32; 3] {
         match self {
             BlockTexture::GrassBrick => [23, 23, 23],
@@ -113,6 +181,64 @@ impl BlockTexture {
             BlockTexture::RockBrick => [46, 46, 46],
             BlockTexture::WaterBrick => [78, 78, 78],
             BlockTexture::FullBrick => [9, 9, 9],
+            BlockTexture::FenceStone => [13, 13, 13],
+            BlockTexture::FenceStonePost => [50, 13, 50],
+            BlockTexture::FenceWood => [14, 14, 14],
+            BlockTexture::FenceWoodPost => [83, 14, 83],
+            BlockTexture::GlassFrame => [16, 16, 16],
+            BlockTexture::GlassPane => [15, 15, 15],
+            BlockTexture::TrackNorthSouth | BlockTexture::TrackEastWest => [70, 71, 71],
+            BlockTexture::TrackNorthEast
+            | BlockTexture::TrackSouthEast
+            | BlockTexture::TrackSouthWest
+            | BlockTexture::TrackNorthWest => [68, 71, 71],
+            BlockTexture::Leaves => [36, 36, 36],
+            BlockTexture::Lava => [32, 32, 32],
+            BlockTexture::RubyOre => [28, 28, 28],
+            BlockTexture::EmeraldOre => [43, 43, 43],
+            BlockTexture::DiamondOre => [55, 55, 55],
+        }
+    }
+
+    /// Quarter turns applied to the top tile, so one track tile can be laid in every direction.
+    /// The straight tile runs north-south and the corner tile joins south and east.
+    pub(crate) fn top_rotation(&self) -> u32 {
+        match self {
+            BlockTexture::TrackEastWest | BlockTexture::TrackSouthWest => 1,
+            BlockTexture::TrackNorthWest => 2,
+            BlockTexture::TrackNorthEast => 3,
+            _ => 0,
+        }
+    }
+
+    /// Tile indexes from a texture pack, with the rotation of the block added to the top tile
+    /// and tinted blocks marked with the neutral tint.
+    pub(crate) fn encode_tiles(&self, [top, side, bottom]: [u32; 3]) -> [u32; 3] {
+        let tint = if self.properties().tinted {
+            NEUTRAL_TINT << TILE_TINT_SHIFT
+        } else {
+            0
+        };
+        [
+            top | self.top_rotation() << TILE_ROTATION_SHIFT | tint,
+            side | tint,
+            bottom | tint,
+        ]
+    }
+
+    /// The color of the block on top down maps.
+    pub fn map_color(&self) -> [u8; 3] {
+        match self {
+            BlockTexture::GrassBrick | BlockTexture::Leaves => [86, 150, 60],
+            BlockTexture::SnowyBrick => [235, 240, 245],
+            BlockTexture::DirtBrick => [120, 85, 55],
+            BlockTexture::SandBrick => [220, 205, 145],
+            BlockTexture::GravelBrick => [130, 125, 120],
+            BlockTexture::StoneBrick => [150, 150, 155],
+            BlockTexture::RockBrick => [95, 90, 90],
+            BlockTexture::WaterBrick => [50, 100, 210],
+            BlockTexture::Lava => [240, 110, 20],
+            _ => [160, 140, 120],
         }
     }
 
@@ -121,3 +247,71 @@ impl BlockTexture {
     }
 }
 
+/// Heights at which columns are drawn darkest and brightest on top down maps.
+const MAP_LOW: i32 = -10;
+const MAP_HIGH: i32 = 50;
+
+/// How much a column's `map_color` is brightened on top down maps. Higher columns are brighter,
+/// and `slope`, the height of the column above its north west neighbour, lights it from there.
+pub fn map_shade(height: i32, slope: i32) -> f32 {
+    let light = (height - MAP_LOW) as f32 / (MAP_HIGH - MAP_LOW) as f32;
+    (0.55 + 0.45 * light.clamp(0.0, 1.0) + 0.08 * slope as f32).clamp(0.3, 1.3)
+}
+
+impl FromStr for BlockTexture {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BlockTexture::ALL
+            .into_iter()
+            .find(|block| format!("{:?}", block) == name)
+            .ok_or_else(|| format!("unknown block '{}'", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layer, quarter turns and tint level of a tile, decoded like `block_material.wgsl` does.
+    fn decode(tile: u32) -> (u32, u32, u32) {
+        (
+            tile & ((1 << TILE_ROTATION_SHIFT) - 1),
+            (tile >> TILE_ROTATION_SHIFT) & 3,
+            (tile >> TILE_TINT_SHIFT) & 7,
+        )
+    }
+
+    #[test]
+    fn tint_levels_cover_dry_to_lush() {
+        assert_eq!(tint_level(0), 1);
+        assert_eq!(tint_level(128), 4);
+        assert_eq!(tint_level(255), 7);
+        assert!((0..=255).all(|moisture| (1..=7).contains(&tint_level(moisture))));
+    }
+
+    #[test]
+    fn encoded_tiles_round_trip() {
+        let [top, side, bottom] = BlockTexture::TrackNorthWest.encode_tiles([68, 71, 71]);
+        assert_eq!(decode(top), (68, 2, 0));
+        assert_eq!(decode(side), (71, 0, 0));
+        assert_eq!(decode(bottom), (71, 0, 0));
+
+        let [top, side, _] = BlockTexture::GrassBrick.encode_tiles([23, 84, 9]);
+        assert_eq!(decode(top), (23, 0, NEUTRAL_TINT));
+        assert_eq!(decode(side), (84, 0, NEUTRAL_TINT));
+    }
+
+    #[test]
+    fn retint_keeps_layer_and_rotation() {
+        let [top, ..] = BlockTexture::GrassBrick.encode_tiles([23, 23, 23]);
+        let rotated = top | 3 << TILE_ROTATION_SHIFT;
+        for moisture in [0, 100, 255] {
+            let tint = tint_level(moisture);
+            assert_eq!(decode(retint_tile(rotated, tint)), (23, 3, tint));
+        }
+        // Untinted tiles stay untinted
+        let [top, ..] = BlockTexture::TrackEastWest.encode_tiles([70, 71, 71]);
+        assert_eq!(retint_tile(top, 7), top);
+    }
+}
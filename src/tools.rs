@@ -0,0 +1,58 @@
+use crate::camera::DemoCameraSet;
+use crate::clipboard::{self, Clipboard};
+use crate::cursor::{CursorEditSettings, CursorSet};
+use crate::hotbar::{self, Hotbar};
+use crate::selection::{self, Selection};
+use crate::shapes::{self, ShapeTool};
+use crate::{input, lighting, texture_pack};
+use bevy::prelude::*;
+
+/// The building tools on top of the cursor: the box selection, the shape brushes, copy and paste
+/// and the hotbar of blocks to place. Needs the terrain and cursor plugins.
+#[derive(Default)]
+pub struct EditToolsPlugin;
+
+impl Plugin for EditToolsPlugin {
+    fn build(&self, app: &mut App) {
+        input::add_actions(app);
+        app.init_resource::<Selection>()
+            .init_resource::<ShapeTool>()
+            .init_resource::<Clipboard>()
+            .init_resource::<Hotbar>()
+            .add_systems(
+                PostStartup,
+                (selection::setup_selection_box, hotbar::setup_hotbar),
+            )
+            .add_systems(
+                Update,
+                (
+                    // The camera holds still while a selection is dragged
+                    selection::drag_selection
+                        .after(CursorSet::Trace)
+                        .before(DemoCameraSet),
+                    (
+                        shapes::shape_tool_keys,
+                        clipboard::clipboard_keys,
+                        clipboard::paste,
+                        shapes::place_shape,
+                        selection::edit_selection,
+                        hotbar::pick_cursor_block
+                            .run_if(|settings: Res<CursorEditSettings>| settings.editing),
+                    )
+                        .chain()
+                        .in_set(CursorSet::Tools)
+                        .before(lighting::update_block_light),
+                    selection::update_selection_box.after(selection::drag_selection),
+                    shapes::draw_shape_preview.after(shapes::place_shape),
+                    clipboard::draw_paste_preview.after(clipboard::paste),
+                    (
+                        hotbar::update_hotbar_icons,
+                        hotbar::select_hotbar_block,
+                        hotbar::update_hotbar,
+                    )
+                        .chain()
+                        .after(texture_pack::apply_texture_pack),
+                ),
+            );
+    }
+}
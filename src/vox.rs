@@ -0,0 +1,215 @@
+use crate::map::{Map, MapNode, NodeType};
+use crate::textures::BlockTexture;
+
+/// Voxel z of height 0, every height of a map fits in a model.
+const SEA_LEVEL: i32 = 128;
+/// Largest model MagicaVoxel loads along each axis.
+const MAX_MODEL_SIZE: u32 = 256;
+const VERSION: u32 = 150;
+/// Palette index of the voxels below the surface. Surfaces have the index of their type in
+/// `NodeType::ALL` plus one, palette indexes start at 1.
+const GROUND_INDEX: u8 = NodeType::ALL.len() as u8 + 1;
+const UNUSED_COLOR: [u8; 4] = [128, 128, 128, 255];
+
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend(id);
+    out.extend((content.len() as u32).to_le_bytes());
+    out.extend((children.len() as u32).to_le_bytes());
+    out.extend(content);
+    out.extend(children);
+}
+
+fn palette_index(surface: NodeType) -> u8 {
+    NodeType::ALL
+        .iter()
+        .position(|t| *t == surface)
+        .unwrap_or_default() as u8
+        + 1
+}
+
+/// The map as a MagicaVoxel model, with x and z of the map along x and y and heights up
+/// along z. Columns are solid from the lowest column of the map up.
+pub fn encode(map: &Map) -> Result<Vec<u8>, String> {
+    let extent = map.extent();
+    if extent.x > MAX_MODEL_SIZE || extent.y > MAX_MODEL_SIZE {
+        return Err(format!(
+            "a .vox model is at most {0}x{0} columns, the map is {1}x{2}",
+            MAX_MODEL_SIZE, extent.x, extent.y
+        ));
+    }
+    let nodes = || map.map.iter().flatten();
+    let lowest = nodes().map(|node| node.height as i32).min().unwrap_or(0);
+    let mut voxels = Vec::new();
+    for (x, row) in map.map.iter().enumerate() {
+        for (z, node) in row.iter().enumerate() {
+            let height = node.height as i32;
+            for y in lowest..=height {
+                let index = if y == height {
+                    palette_index(node.surface_type)
+                } else {
+                    GROUND_INDEX
+                };
+                voxels.extend([x as u8, z as u8, (y + SEA_LEVEL) as u8, index]);
+            }
+        }
+    }
+
+    let mut size = Vec::new();
+    for axis in [extent.x, extent.y, MAX_MODEL_SIZE] {
+        size.extend(axis.to_le_bytes());
+    }
+    let mut xyzi = ((voxels.len() / 4) as u32).to_le_bytes().to_vec();
+    xyzi.extend(voxels);
+    let mut palette = Vec::new();
+    for entry in 1..=256 {
+        let color = match NodeType::ALL.get(entry - 1) {
+            Some(surface) => surface.block().map_color(),
+            None if entry == GROUND_INDEX as usize => BlockTexture::FullBrick.map_color(),
+            None => {
+                palette.extend(UNUSED_COLOR);
+                continue;
+            }
+        };
+        palette.extend(color);
+        palette.push(255);
+    }
+    let mut children = Vec::new();
+    chunk(&mut children, b"SIZE", &size, &[]);
+    chunk(&mut children, b"XYZI", &xyzi, &[]);
+    chunk(&mut children, b"RGBA", &palette, &[]);
+
+    let mut out = b"VOX ".to_vec();
+    out.extend(VERSION.to_le_bytes());
+    chunk(&mut out, b"MAIN", &[], &children);
+    Ok(out)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32, String> {
+    bytes
+        .get(at..at + 4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .ok_or_else(|| "the file ends in the middle of a chunk".to_string())
+}
+
+/// The surface closest to a palette color, or the surface at a palette index without a palette.
+fn surface_of(index: u8, palette: Option<&[u8]>) -> NodeType {
+    let entry = index.saturating_sub(1) as usize;
+    match palette.and_then(|palette| palette.get(entry * 4..entry * 4 + 3)) {
+        Some(color) => NodeType::ALL
+            .into_iter()
+            .min_by_key(|surface| {
+                let surface_color = surface.block().map_color();
+                color
+                    .iter()
+                    .zip(surface_color)
+                    .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .unwrap_or_default(),
+        None => NodeType::ALL.get(entry).copied().unwrap_or(NodeType::Stone),
+    }
+}
+
+/// A map from the first model of a MagicaVoxel file. The top voxel of each column is its
+/// surface, columns without voxels are at the lowest height.
+pub fn decode(bytes: &[u8]) -> Result<Map, String> {
+    if !bytes.starts_with(b"VOX ") {
+        return Err("not a MagicaVoxel file".into());
+    }
+    let (mut size, mut voxels, mut palette) = (None, None, None);
+    // The other chunks are the children of the main chunk, read as if they followed it
+    let mut at = 8;
+    while at < bytes.len() {
+        let id = bytes.get(at..at + 4).unwrap_or_default();
+        let content = read_u32(bytes, at + 4)? as usize;
+        let children = read_u32(bytes, at + 8)? as usize;
+        let start = at + 12;
+        let data = bytes
+            .get(start..start + content)
+            .ok_or("the file ends in the middle of a chunk")?;
+        match id {
+            b"MAIN" => {
+                at = start + content;
+                continue;
+            }
+            b"SIZE" if size.is_none() => size = Some((read_u32(data, 0)?, read_u32(data, 4)?)),
+            b"XYZI" if voxels.is_none() => voxels = Some(data.get(4..).unwrap_or_default()),
+            b"RGBA" => palette = Some(data),
+            _ => {}
+        }
+        at = start + content + children;
+    }
+    let (Some((width, depth)), Some(voxels)) = (size, voxels) else {
+        return Err("the file has no model".into());
+    };
+    if width == 0 || depth == 0 || width > MAX_MODEL_SIZE || depth > MAX_MODEL_SIZE {
+        return Err(format!("a {}x{} model can't be a map", width, depth));
+    }
+
+    let mut tops: Vec<Option<(u8, u8)>> = vec![None; (width * depth) as usize];
+    for voxel in voxels.chunks_exact(4) {
+        let [x, y, z, index] = [voxel[0], voxel[1], voxel[2], voxel[3]];
+        if x as u32 >= width || y as u32 >= depth {
+            continue;
+        }
+        let top = &mut tops[x as usize * depth as usize + y as usize];
+        if top.is_none_or(|(top, _)| z > top) {
+            *top = Some((z, index));
+        }
+    }
+    let rows = tops
+        .chunks(depth as usize)
+        .map(|row| {
+            row.iter()
+                .map(|top| match top {
+                    Some((z, index)) => MapNode {
+                        surface_type: surface_of(*index, palette),
+                        height: (*z as i32 - SEA_LEVEL) as i8,
+                        moisture: 0,
+                    },
+                    None => MapNode {
+                        surface_type: NodeType::at_height(i8::MIN),
+                        height: i8::MIN,
+                        moisture: 0,
+                    },
+                })
+                .collect()
+        })
+        .collect();
+    Map::from_rows(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_round_trip() {
+        let mut map = Map::test_map();
+        map.set_surface(bevy::math::IVec3::ZERO, NodeType::Water);
+        map.set_height(bevy::math::IVec3::new(1, 0, 1), -4);
+        let sut = decode(&encode(&map).unwrap()).unwrap();
+        let columns = |map: &Map| -> Vec<(NodeType, i8)> {
+            map.map
+                .iter()
+                .flatten()
+                .map(|node| (node.surface_type, node.height))
+                .collect()
+        };
+        assert_eq!(columns(&sut), columns(&map));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(decode(b"PNG"), Err("not a MagicaVoxel file".into()));
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        chunk(&mut bytes, b"MAIN", &[], &[]);
+        assert_eq!(decode(&bytes), Err("the file has no model".into()));
+        bytes.extend(b"SIZE");
+        assert_eq!(
+            decode(&bytes),
+            Err("the file ends in the middle of a chunk".into())
+        );
+    }
+}
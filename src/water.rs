@@ -0,0 +1,225 @@
+use crate::blocks::is_liquid;
+use crate::editing::WaterChanged;
+use crate::map::{Map, Size};
+use bevy::pbr::{MaterialPipeline, MaterialPipelineKey};
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderDefVal, ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy_voxel_world::prelude::*;
+use bevy_voxel_world::rendering::{vertex_layout, VOXEL_TEXTURE_SHADER_HANDLE};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+/// How far water spreads sideways from where it lands.
+const MAX_SPREAD: u32 = 7;
+
+/// The voxels water from a spring at `source` runs into, at most `volume` of them. Water falls
+/// while there is room below it and spreads out sideways over the ground it lands on.
+/// `open` tells whether water can flow into a voxel.
+pub(crate) fn spring_flow(
+    source: IVec3,
+    open: impl Fn(IVec3) -> bool,
+    volume: usize,
+) -> Vec<IVec3> {
+    let mut flow = Vec::new();
+    let mut seen = HashSet::from([source]);
+    let mut queue = VecDeque::from([(source, 0)]);
+    while let Some((pos, spread)) = queue.pop_front() {
+        if flow.len() >= volume {
+            break;
+        }
+        if !open(pos) {
+            continue;
+        }
+        flow.push(pos);
+        let below = pos + IVec3::NEG_Y;
+        if open(below) {
+            if seen.insert(below) {
+                queue.push_back((below, 0));
+            }
+            continue;
+        }
+        if spread == MAX_SPREAD {
+            continue;
+        }
+        for side in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+            if seen.insert(pos + side) {
+                queue.push_back((pos + side, spread + 1));
+            }
+        }
+    }
+    flow
+}
+
+const RED: u8 = 0;
+const GREEN: u8 = 1;
+pub(crate) const BLUE: u8 = 2;
+
+/// Config of the water world, rendered with its own material above the terrain of the same map.
+#[derive(Resource, Clone)]
+pub struct WaterWorld {
+    pub(crate) static_map: Arc<Map>,
+}
+
+impl Default for WaterWorld {
+    fn default() -> Self {
+        Self {
+            static_map: Arc::new(Map::noise_map(Size::new(20, 20))),
+        }
+    }
+}
+
+impl VoxelWorldConfig for WaterWorld {
+    type MaterialIndex = u8;
+
+    fn texture_index_mapper(&self) -> Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync> {
+        Arc::new(|vox_mat: u8| match vox_mat {
+            RED => [1, 1, 1],
+            GREEN => [2, 2, 2],
+            BLUE => [3, 3, 3],
+            _ => [4, 4, 4],
+        })
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
+        let map = self.static_map.clone();
+        Box::new(move |_chunk_pos| get_water_voxel_fn(map.clone()))
+    }
+}
+
+fn get_water_voxel_fn(world_map: Arc<Map>) -> VoxelLookupFn {
+    Box::new(move |pos: IVec3| water_voxel(&world_map, pos))
+}
+
+/// The generated water voxel at a position, before any edits.
+pub(crate) fn water_voxel(world_map: &Map, pos: IVec3) -> WorldVoxel {
+    match world_map.voxel_at(pos) {
+        WorldVoxel::Unset => WorldVoxel::Unset,
+        voxel if is_liquid(voxel) => WorldVoxel::Solid(BLUE),
+        _ => WorldVoxel::Air,
+    }
+}
+
+impl WaterWorld {
+    pub fn new(static_map: Arc<Map>) -> Self {
+        Self { static_map }
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+struct WaterVoxelMaterial {
+    // We're not using any uniforms in this example
+    _unused: u32,
+}
+
+impl Material for WaterVoxelMaterial {
+    fn vertex_shader() -> ShaderRef {
+        // You can use the default shader from bevy_voxel_world for the vertex shader for simplicity
+        VOXEL_TEXTURE_SHADER_HANDLE.into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "water_material.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&ShaderDefVal::Bool("PREPASS_PIPELINE".into(), true))
+        {
+            return Ok(());
+        }
+
+        // Use `vertex_layout()` from `bevy_voxel_world` to get the correct vertex layout
+        let vertex_layout = layout.0.get_layout(&vertex_layout())?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        Ok(())
+    }
+}
+
+/// Water added on startup, on top of the water of the map.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WaterSettings {
+    /// Half the width of the square of water filled in around the origin at height 0, no
+    /// water for 0.
+    pub pool: i32,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self { pool: 20 }
+    }
+}
+
+/// The water world of a map with its material.
+pub struct WaterPlugin {
+    pub map: Arc<Map>,
+    pub settings: WaterSettings,
+}
+
+impl WaterPlugin {
+    pub fn new(map: Arc<Map>) -> Self {
+        Self {
+            map,
+            settings: WaterSettings::default(),
+        }
+    }
+}
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_plugins(MaterialPlugin::<WaterVoxelMaterial>::default())
+            .add_plugins(
+                VoxelWorldPlugin::with_config(WaterWorld::new(self.map.clone()))
+                    .with_material(WaterVoxelMaterial { _unused: 0 }),
+            )
+            .add_event::<WaterChanged>()
+            .add_systems(Startup, fill_pool);
+    }
+}
+
+/// Fill the pool of the settings with water.
+fn fill_pool(mut water_world: VoxelWorld<WaterWorld>, settings: Res<WaterSettings>) {
+    for i in -settings.pool..settings.pool {
+        for j in -settings.pool..settings.pool {
+            water_world.set_voxel(IVec3::new(i, 0, j), WorldVoxel::Solid(BLUE));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_then_spreads() {
+        // Flat ground at y = 0
+        let sut = spring_flow(IVec3::new(0, 3, 0), |pos| pos.y > 0, 1000);
+        assert_eq!(
+            sut[..3],
+            [
+                IVec3::new(0, 3, 0),
+                IVec3::new(0, 2, 0),
+                IVec3::new(0, 1, 0)
+            ]
+        );
+        let spread = sut.iter().filter(|pos| pos.y == 1).count();
+        // A diamond of radius MAX_SPREAD
+        assert_eq!(spread, 2 * 7 * 8 + 1);
+    }
+
+    #[test]
+    fn stops_at_volume() {
+        let sut = spring_flow(IVec3::ZERO, |pos| pos.y > -100, 10);
+        assert_eq!(sut.len(), 10);
+        assert!(spring_flow(IVec3::ZERO, |_| false, 10).is_empty());
+    }
+}